@@ -19,8 +19,9 @@ defmodule Shmup.Application do
       # Start to serve requests, typically the last entry
       ShmupWeb.Endpoint,
       ShmupWeb.Presence,
-      # Start yarnballs game loop
-      {ShmupWeb.GameLoop, Yarnballs.State.init()},
+      # Start yarnballs game loops, one per room as players join
+      {Registry, keys: :unique, name: ShmupWeb.GameLoopRegistry},
+      {DynamicSupervisor, name: ShmupWeb.GameLoopSupervisor, strategy: :one_for_one},
       # Track users leaving game
       {ShmupWeb.ChannelWatcher, :games}
     ]
@@ -5,7 +5,7 @@ defmodule ShmupWeb.UserSocket do
   alias Shmup.Auth.User
 
   ## Channels
-  channel("yarnballs:x", ShmupWeb.YarnballsChannel)
+  channel("yarnballs:*", ShmupWeb.YarnballsChannel)
 
   # Socket params are passed from the client and can
   # be used to verify and authenticate a user. After
@@ -4,9 +4,29 @@ defmodule ShmupWeb.YarnballsChannel do
   use Phoenix.Channel
   alias ShmupWeb.Presence
 
-  @events ["requested_state"]
+  # clients name weapons as strings, which are not turned into atoms as they come
+  @weapons %{
+    "single" => :single,
+    "spread" => :spread,
+    "rapid" => :rapid,
+    "laser" => :laser,
+    "homing" => :homing
+  }
+
+  # every topic starts a room, so only short, plain names are taken
+  @room_id ~r/\A[A-Za-z0-9_-]{1,32}\z/
+
+  # `message` may name the team to play in, as `%{"team" => team}`
+  def join("yarnballs:" <> room_id, message, socket) do
+    if Regex.match?(@room_id, room_id) do
+      join_room(room_id, message, socket)
+    else
+      {:error, %{reason: "invalid room"}}
+    end
+  end
 
-  def join("yarnballs:" <> room_id, _message, socket) do
+  defp join_room(room_id, message, socket) do
+    {:ok, _pid} = ShmupWeb.GameLoop.ensure_started(room_id, room_opts(room_id))
     send(self(), :after_join)
 
     user_id = socket.assigns.user.id
@@ -18,34 +38,68 @@ defmodule ShmupWeb.YarnballsChannel do
         {__MODULE__, :leave, [room_id, user_id]}
       )
 
-    {:ok, %{events: @events}, socket}
+    socket =
+      socket
+      |> assign(:room_id, room_id)
+      |> assign(:team, team(message))
+
+    {:ok, %{events: events()}, socket}
+  end
+
+  # see `ShmupWeb.GameLoop` for what rooms push
+  defp events do
+    if Application.get_env(:shmup, :yarnballs_snapshots, false),
+      do: ["snapshot"],
+      else: ["requested_state"]
+  end
+
+  # Per-room options, see `Yarnballs.State.init/1`, for example
+  #
+  #     config :shmup, :yarnballs_rooms, %{"mobile" => [width: 480, height: 320]}
+  #
+  defp room_opts(room_id) do
+    :shmup
+    |> Application.get_env(:yarnballs_rooms, %{})
+    |> Map.get(room_id, [])
   end
 
   def handle_info(:after_join, socket) do
     user = socket.assigns.user
 
-    Presence.track(socket, user.id, %{name: user.name})
+    Presence.track(socket, user.id, %{name: user.name, team: socket.assigns.team})
 
     {:noreply, socket}
   end
 
   def handle_in("turned_ship", %{"clockwise" => clockwise}, socket) do
-    ShmupWeb.GameLoop.turned_ship(socket.assigns.user.id, clockwise)
+    ShmupWeb.GameLoop.turned_ship(socket.assigns.room_id, socket.assigns.user.id, clockwise)
     {:noreply, socket}
   end
 
   def handle_in("thrusted_ship", %{}, socket) do
-    ShmupWeb.GameLoop.thrusted_ship(socket.assigns.user.id)
+    ShmupWeb.GameLoop.thrusted_ship(socket.assigns.room_id, socket.assigns.user.id)
     {:noreply, socket}
   end
 
   def handle_in("fired_shot", %{}, socket) do
-    ShmupWeb.GameLoop.fire_missile(socket.assigns.user.id)
+    ShmupWeb.GameLoop.fire_missile(socket.assigns.room_id, socket.assigns.user.id)
     {:noreply, socket}
   end
 
-  def leave(_room_id, user_id) do
-    ShmupWeb.GameLoop.leave(user_id)
+  def handle_in("switched_weapon", %{"weapon" => weapon}, socket) do
+    case Map.fetch(@weapons, weapon) do
+      {:ok, weapon} ->
+        ShmupWeb.GameLoop.switch_weapon(socket.assigns.room_id, socket.assigns.user.id, weapon)
+
+      :error ->
+        :ok
+    end
+
+    {:noreply, socket}
+  end
+
+  def leave(room_id, user_id) do
+    ShmupWeb.GameLoop.leave(room_id, user_id)
   end
 
   intercept(["presence_diff"])
@@ -54,18 +108,26 @@ defmodule ShmupWeb.YarnballsChannel do
     Enum.each(
       joins,
       fn {user_id, %{metas: metas}} ->
-        ShmupWeb.GameLoop.join(user_id, name_from_metas(metas))
+        ShmupWeb.GameLoop.join(
+          socket.assigns.room_id,
+          user_id,
+          from_metas(metas, :name),
+          from_metas(metas, :team)
+        )
       end
     )
 
-    Enum.each(Map.keys(leaves), &ShmupWeb.GameLoop.leave/1)
+    Enum.each(Map.keys(leaves), &ShmupWeb.GameLoop.leave(socket.assigns.room_id, &1))
 
     {:noreply, socket}
   end
 
-  defp name_from_metas(metas) do
+  defp from_metas(metas, key) do
     metas
     |> Enum.reduce(%{}, fn meta, merged -> Map.merge(meta, merged) end)
-    |> Map.get(:name)
+    |> Map.get(key)
   end
+
+  defp team(%{"team" => team}) when is_binary(team) and team != "", do: team
+  defp team(_message), do: nil
 end
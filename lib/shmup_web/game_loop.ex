@@ -1,14 +1,43 @@
 defmodule ShmupWeb.GameLoop do
   @moduledoc false
 
-  use Yarnballs.GameLoop
+  use Yarnballs.GameLoop,
+    registry: ShmupWeb.GameLoopRegistry,
+    supervisor: ShmupWeb.GameLoopSupervisor
 
-  def handle_new_state(state), do: broadcast!(state)
+  def handle_new_state(room_id, state) do
+    broadcast!(room_id, state)
+    announce!(room_id, state)
+  end
 
-  defp broadcast!(state) do
-    msg = %{state: state}
-    ShmupWeb.Endpoint.broadcast!(topic(), "requested_state", msg)
+  # Rooms push the rendered state as JSON unless told to push binary snapshots instead, with
+  #
+  #     config :shmup, :yarnballs_snapshots, true
+  #
+  defp broadcast!(room_id, state) do
+    if Application.get_env(:shmup, :yarnballs_snapshots, false) do
+      {:ok, snapshot} = Yarnballs.State.snapshot(state)
+      ShmupWeb.Endpoint.broadcast!(topic(room_id), "snapshot", {:binary, snapshot})
+    else
+      {:ok, rendered} = Yarnballs.State.render(state)
+      msg = %{state: rendered}
+      ShmupWeb.Endpoint.broadcast!(topic(room_id), "requested_state", msg)
+    end
   end
 
-  defp topic, do: "yarnballs:x"
+  defp announce!(room_id, state) do
+    case Yarnballs.State.drain_events(state) do
+      {:ok, []} ->
+        :ok
+
+      {:ok, events} ->
+        msg = %{events: Enum.map(events, &event/1)}
+        ShmupWeb.Endpoint.broadcast!(topic(room_id), "events", msg)
+    end
+  end
+
+  # tagged tuples do not make it into JSON
+  defp event({kind, fields}), do: Map.put(fields, :kind, kind)
+
+  defp topic(room_id), do: "yarnballs:" <> room_id
 end
@@ -0,0 +1,136 @@
+// Timings of a world tick at the enemy counts the late levels produce, and beyond. Worlds are
+// synthetic: ships, missiles and enemies are scattered across the arena up front rather than
+// played into, see `State::populate`. Run with `cargo bench`, no Erlang needed.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use yarnballs::config::Config;
+use yarnballs::state::State;
+
+struct Load {
+    name: &'static str,
+    ships: usize,
+    missiles: usize,
+    enemies: usize,
+    // picks the level enemies are drawn from, see `levels.toml`
+    score: i64,
+}
+
+const LOADS: [Load; 6] = [
+    Load {
+        name: "madness",
+        ships: 8,
+        missiles: 100,
+        enemies: 50,
+        score: 1000,
+    },
+    Load {
+        name: "overkill",
+        ships: 8,
+        missiles: 100,
+        enemies: 500,
+        score: 2000,
+    },
+    Load {
+        name: "overbounce",
+        ships: 8,
+        missiles: 100,
+        enemies: 500,
+        score: 5000,
+    },
+    Load {
+        name: "overkill-1000",
+        ships: 8,
+        missiles: 200,
+        enemies: 1000,
+        score: 2000,
+    },
+    Load {
+        name: "overkill-2000",
+        ships: 16,
+        missiles: 400,
+        enemies: 2000,
+        score: 2000,
+    },
+    Load {
+        name: "crowded",
+        ships: 64,
+        missiles: 1000,
+        enemies: 500,
+        score: 2000,
+    },
+];
+
+fn world(load: &Load) -> State {
+    let mut state = State::init(Config {
+        seed: Some(1),
+        ..Config::default()
+    })
+    .expect("default config is valid");
+    state.populate(load.ships, load.missiles, load.enemies, load.score);
+    state
+}
+
+fn update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update");
+    for load in &LOADS {
+        group.bench_with_input(BenchmarkId::from_parameter(load.name), load, |b, load| {
+            b.iter_batched_ref(
+                || world(load),
+                |state| state.update(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn collisions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collisions");
+    for load in &LOADS {
+        group.bench_with_input(BenchmarkId::from_parameter(load.name), load, |b, load| {
+            b.iter_batched_ref(
+                || {
+                    // grow the grid's buffers first, as a running world would have
+                    let mut state = world(load);
+                    state.update_collisions();
+                    state
+                },
+                |state| state.update_collisions(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn snapshot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot");
+    for load in &LOADS {
+        let state = world(load);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(load.name),
+            &state,
+            |b, state| b.iter(|| state.snapshot()),
+        );
+    }
+    group.finish();
+}
+
+// A delta one tick apart, the common case for a client that keeps up.
+fn delta(c: &mut Criterion) {
+    let mut group = c.benchmark_group("delta");
+    for load in &LOADS {
+        let mut state = world(load);
+        let prev = state.frame();
+        state.update();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(load.name),
+            &state,
+            |b, state| b.iter(|| state.frame().delta(&prev)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, update, collisions, snapshot, delta);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+use crate::error::Error;
+
+// The size of the playing field in pixels. Ships wrap around its edges, enemies spawn just
+// outside of it and are dropped once they drift too far away from it.
+#[derive(Clone, Copy, Default)]
+pub struct Arena {
+    pub width: f64,
+    pub height: f64,
+}
+
+// Matches the canvas the web client draws on.
+const DEFAULT_WIDTH: f64 = 640.;
+const DEFAULT_HEIGHT: f64 = 480.;
+
+// Positions are sent to clients as quarter pixels in an i16, see `snapshot.rs`.
+const MAX_DIM: f64 = 8000.;
+
+impl Arena {
+    pub fn init(width: Option<f64>, height: Option<f64>) -> Result<Self, Error> {
+        let width = width.unwrap_or(DEFAULT_WIDTH);
+        let height = height.unwrap_or(DEFAULT_HEIGHT);
+
+        // also rejects NaN
+        let valid = |dim: f64| (1. ..=MAX_DIM).contains(&dim);
+        if valid(width) && valid(height) {
+            Ok(Self { width, height })
+        } else {
+            Err(Error::InvalidArena { width, height })
+        }
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (self.width / 2., self.height / 2.)
+    }
+
+    // The copy of `to`, shifted by whole widths and heights, that is closest to `from`. Ships
+    // wrap around the edges, so this is where they see something.
+    pub fn nearest(&self, from: (f64, f64), to: (f64, f64)) -> (f64, f64) {
+        let shift = |from: f64, to: f64, limit: f64| to - ((to - from) / limit).round() * limit;
+        (
+            shift(from.0, to.0, self.width),
+            shift(from.1, to.1, self.height),
+        )
+    }
+
+    // Whether a point lies further than `padding` outside of the arena.
+    pub fn is_outside(&self, x: f64, y: f64, padding: f64) -> bool {
+        x < -padding || y < -padding || x > self.width + padding || y > self.height + padding
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn init_rejects_sizes_out_of_range() {
+        assert!(Arena::init(None, None).is_ok());
+        assert!(Arena::init(Some(0.), None).is_err());
+        assert!(Arena::init(None, Some(MAX_DIM + 1.)).is_err());
+        assert!(Arena::init(Some(f64::NAN), None).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn nearest_is_within_half_the_arena(
+            width in 1. ..MAX_DIM,
+            height in 1. ..MAX_DIM,
+            from in (-1e4..1e4f64, -1e4..1e4f64),
+            to in (-1e4..1e4f64, -1e4..1e4f64),
+        ) {
+            let arena = Arena::init(Some(width), Some(height)).unwrap();
+            let (x, y) = arena.nearest(from, to);
+            prop_assert!((x - from.0).abs() <= width / 2. + 1e-6);
+            prop_assert!((y - from.1).abs() <= height / 2. + 1e-6);
+
+            let laps = |moved: f64, limit: f64| (moved / limit - (moved / limit).round()).abs();
+            prop_assert!(laps(x - to.0, width) < 1e-6);
+            prop_assert!(laps(y - to.1, height) < 1e-6);
+        }
+    }
+}
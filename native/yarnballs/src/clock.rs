@@ -0,0 +1,94 @@
+// Simulation time in milliseconds, advanced explicitly by the caller rather than read from the
+// wall clock so that the same inputs always step the world the same way.
+//
+// Time passes in fixed steps: whatever the caller hands over is accumulated, and taken out one
+// step at a time, so bodies move, slow down and stop turning the same way whatever the tick rate.
+pub struct Clock {
+    now: i64,
+    // time handed over but not stepped through yet
+    accumulated: i64,
+}
+
+// Matches the 16 ms tick scheduled by `Yarnballs.GameLoop`, which friction was tuned for.
+pub const STEP: i64 = 16;
+// Time past this many steps in a single update is dropped, so a world that fell behind slows
+// down for a moment instead of spending ever longer catching up.
+const MAX_STEPS: i64 = 16;
+
+impl Clock {
+    pub fn init() -> Self {
+        Self {
+            now: 0,
+            accumulated: 0,
+        }
+    }
+
+    pub fn now(&self) -> i64 {
+        self.now
+    }
+
+    // Hand over a caller-supplied amount of time. Time never runs backwards.
+    pub fn accumulate(&mut self, dt: i64) {
+        self.accumulated = self
+            .accumulated
+            .saturating_add(dt.max(0))
+            .min(STEP * MAX_STEPS);
+    }
+
+    // Take a step out of the accumulated time, if there is enough of it left.
+    pub fn step(&mut self) -> bool {
+        if self.accumulated < STEP {
+            return false;
+        }
+
+        self.accumulated -= STEP;
+        self.now += STEP;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn steps(clock: &mut Clock, dt: i64) -> usize {
+        clock.accumulate(dt);
+        std::iter::from_fn(|| clock.step().then_some(())).count()
+    }
+
+    #[test]
+    fn leftover_time_carries_over() {
+        let mut clock = Clock::init();
+        assert_eq!(steps(&mut clock, 10), 0);
+        assert_eq!(steps(&mut clock, 10), 1);
+        assert_eq!(steps(&mut clock, 28), 2);
+        assert_eq!(clock.now(), 3 * STEP);
+        assert_eq!(steps(&mut clock, -100), 0);
+    }
+
+    #[test]
+    fn long_updates_are_capped() {
+        let mut clock = Clock::init();
+        assert_eq!(steps(&mut clock, i64::MAX), MAX_STEPS as usize);
+        assert_eq!(steps(&mut clock, 0), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn time_passes_in_whole_steps(dts in prop::collection::vec(-50..=240i64, 0..50)) {
+            let mut clock = Clock::init();
+            let mut handed_over = 0;
+            for dt in dts {
+                let taken = steps(&mut clock, dt);
+                prop_assert!(taken <= MAX_STEPS as usize);
+                prop_assert_eq!(clock.now() % STEP, 0);
+
+                // nothing is dropped below the cap
+                handed_over += dt.max(0);
+                prop_assert_eq!(clock.now() + clock.accumulated, handed_over);
+                prop_assert!(clock.accumulated < STEP);
+            }
+        }
+    }
+}
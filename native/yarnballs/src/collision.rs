@@ -1,5 +1,8 @@
+use crate::arena::Arena;
+use crate::error::Error;
+use crate::power_up::{self, PowerUp};
 use crate::{enemy, enemy::Enemy, missile, missile::Missile, ship, ship::Ship};
-use std::collections::{hash_map::Values, HashMap};
+use std::ops::Range;
 
 fn distance(p1: (f64, f64), p2: (f64, f64)) -> f64 {
     let (x1, y1) = p1;
@@ -11,140 +14,691 @@ fn distance(p1: (f64, f64), p2: (f64, f64)) -> f64 {
 pub trait Circle {
     fn center(&self) -> (f64, f64);
     fn radius(&self) -> f64;
+
+    // The center at the start of the last tick. Bodies that do not move can leave it out.
+    fn prev_center(&self) -> (f64, f64) {
+        self.center()
+    }
 }
 
+/*
+Whether two circles touched at any point during the last tick, taking both to have moved in a
+straight line from their previous center to their current one.
+
+Testing only where they ended up lets a missile skip over a small rock when a tick is long
+enough, so instead the second circle is followed relative to the first and checked at its
+closest approach.
+*/
 pub fn collided<T: Circle, U: Circle>(circle1: &T, circle2: &U) -> bool {
-    let dist = distance(circle1.center(), circle2.center());
+    let ((px1, py1), (x1, y1)) = (circle1.prev_center(), circle1.center());
+    let ((px2, py2), (x2, y2)) = (circle2.prev_center(), circle2.center());
+    let (start_x, start_y) = (px2 - px1, py2 - py1);
+    let (move_x, move_y) = ((x2 - px2) - (x1 - px1), (y2 - py2) - (y1 - py1));
+
+    let len = move_x.powi(2) + move_y.powi(2);
+    let t = if len > 0. {
+        (-(start_x * move_x + start_y * move_y) / len).clamp(0., 1.)
+    } else {
+        0.
+    };
+    let dist = distance((0., 0.), (start_x + t * move_x, start_y + t * move_y));
 
     dist <= circle1.radius() + circle2.radius()
 }
 
-struct Rectangle {
-    min_x: i64,
-    max_x: i64,
-    min_y: i64,
-    max_y: i64,
+// A circle copied out of its entity, so that the grid does not borrow the entities across ticks.
+struct Body<K> {
+    key: K,
+    center: (f64, f64),
+    prev_center: (f64, f64),
+    radius: f64,
 }
 
-fn to_rect<T: Circle>(circle: &T) -> Rectangle {
-    let (x, y) = circle.center();
-    let r = circle.radius();
+impl<K> Circle for Body<K> {
+    fn center(&self) -> (f64, f64) {
+        self.center
+    }
+
+    fn radius(&self) -> f64 {
+        self.radius
+    }
 
-    Rectangle {
-        min_x: (x - r).floor() as i64,
-        max_x: (x + r).ceil() as i64,
-        min_y: (y - r).floor() as i64,
-        max_y: (y + r).ceil() as i64,
+    fn prev_center(&self) -> (f64, f64) {
+        self.prev_center
     }
 }
 
+impl<K> Body<K> {
+    fn of<T: Circle>(key: K, circle: &T) -> Self {
+        Self {
+            key,
+            center: circle.center(),
+            prev_center: circle.prev_center(),
+            radius: circle.radius(),
+        }
+    }
+}
+
+// Indices of the bodies that overlap a cell.
 #[derive(Default)]
-pub struct Space<'a> {
-    missiles: Vec<&'a Missile>,
-    enemies: Vec<&'a Enemy>,
-    ships: Vec<&'a Ship>,
+struct Cell {
+    missiles: Vec<u32>,
+    enemies: Vec<u32>,
+    ships: Vec<u32>,
+    power_ups: Vec<u32>,
 }
 
-impl<'a> Space<'a> {
-    pub fn enemy_missile_collisions(&self) -> Vec<EnemyMissileCollision> {
-        self.enemies
-            .iter()
-            .flat_map(|e| {
-                self.missiles
-                    .iter()
-                    .filter(|m| collided(*e, **m))
-                    .map(|m| (e.id(), m.id))
-                    .collect::<Vec<EnemyMissileCollision>>()
-            })
-            .collect()
+impl Cell {
+    fn is_empty(&self) -> bool {
+        self.missiles.is_empty()
+            && self.enemies.is_empty()
+            && self.ships.is_empty()
+            && self.power_ups.is_empty()
     }
 
-    pub fn ship_enemy_collisions(&self) -> Vec<ShipEnemyCollision> {
-        self.ships
-            .iter()
-            .flat_map(|s| {
-                self.enemies
-                    .iter()
-                    .filter(|e| !s.is_dead() && collided(*s, **e))
-                    // TODO: avoid cloning
-                    .map(|e| (s.id.clone(), e.id()))
-                    .collect::<Vec<ShipEnemyCollision>>()
-            })
-            .collect()
+    fn clear(&mut self) {
+        self.missiles.clear();
+        self.enemies.clear();
+        self.ships.clear();
+        self.power_ups.clear();
     }
 }
 
-pub struct SpatialHash<'a> {
-    cell_size: u32,
-    bodies: HashMap<(i64, i64), Space<'a>>,
-}
+/*
+Broad phase: a fixed grid of square cells laid over the arena. The state keeps one around and
+clears it every tick instead of building a new one, so once the buffers have grown to the size
+of the world nothing is allocated any more.
 
-pub fn new_spatial_hash<'a>() -> SpatialHash<'a> {
-    SpatialHash {
-        cell_size: 50,
-        bodies: HashMap::new(),
-    }
-}
+Bodies go into every cell they swept through during the last tick. Bodies outside of the arena
+go into the nearest edge cells. That makes the edge cells a bit busier, but two bodies that
+touched always share a cell.
 
-enum Body<'a> {
-    Missile(&'a Missile),
-    Enemy(&'a Enemy),
-    Ship(&'a Ship),
+Ships wrap around the edges of the arena, so one that is partly across an edge is drawn on both
+sides of it. It is inserted on the far side too, shifted by the arena size, so it can be hit
+where it is drawn.
+*/
+#[derive(Default)]
+pub struct Grid {
+    arena: Arena,
+    cell_size: f64,
+    cols: usize,
+    rows: usize,
+    cells: Vec<Cell>,
+    // cells that are not empty, so clearing does not visit every cell
+    occupied: Vec<usize>,
+    missiles: Vec<Body<missile::ID>>,
+    enemies: Vec<Body<enemy::ID>>,
+    ships: Vec<Body<ship::ID>>,
+    power_ups: Vec<Body<power_up::ID>>,
+    enemy_missile_collisions: Vec<EnemyMissileCollision>,
+    ship_enemy_collisions: Vec<ShipEnemyCollision>,
+    ship_ship_collisions: Vec<ShipShipCollision>,
+    ship_missile_collisions: Vec<ShipMissileCollision>,
+    ship_power_up_collisions: Vec<ShipPowerUpCollision>,
 }
 
-impl<'a> SpatialHash<'a> {
-    fn hash_dim(&self, x: i64) -> i64 {
-        (x as f64 / self.cell_size as f64).floor() as i64
+pub const DEFAULT_CELL_SIZE: f64 = 50.;
+// Keeps a small cell size on a large arena from allocating an unreasonable grid.
+const MAX_CELLS: f64 = 65536.;
+
+impl Grid {
+    pub fn init(arena: Arena, cell_size: Option<f64>) -> Result<Self, Error> {
+        let cell_size = cell_size.unwrap_or(DEFAULT_CELL_SIZE);
+        let cols = (arena.width / cell_size).ceil();
+        let rows = (arena.height / cell_size).ceil();
+        // also rejects NaN
+        if !(cell_size >= 1. && cols * rows <= MAX_CELLS) {
+            return Err(Error::InvalidCellSize(cell_size));
+        }
+
+        let (cols, rows) = (cols as usize, rows as usize);
+        Ok(Self {
+            arena,
+            cell_size,
+            cols,
+            rows,
+            cells: (0..cols * rows).map(|_| Cell::default()).collect(),
+            ..Self::default()
+        })
+    }
+
+    pub fn clear(&mut self) {
+        for &i in &self.occupied {
+            self.cells[i].clear();
+        }
+        self.occupied.clear();
+        self.missiles.clear();
+        self.enemies.clear();
+        self.ships.clear();
+        self.power_ups.clear();
+        self.enemy_missile_collisions.clear();
+        self.ship_enemy_collisions.clear();
+        self.ship_ship_collisions.clear();
+        self.ship_missile_collisions.clear();
+        self.ship_power_up_collisions.clear();
     }
 
-    fn hash(&self, point: (i64, i64)) -> (i64, i64) {
-        let (x, y) = point;
-        (self.hash_dim(x), self.hash_dim(y))
+    // The cells a circle swept through, clamped to the grid.
+    fn span<T: Circle>(&self, circle: &T) -> (Range<usize>, Range<usize>) {
+        // a float to usize cast saturates, and turns NaN into 0
+        let dim = |v: f64, count: usize| ((v / self.cell_size).floor() as usize).min(count - 1);
+        let ((px, py), (x, y), r) = (circle.prev_center(), circle.center(), circle.radius());
+        (
+            dim(px.min(x) - r, self.cols)..dim(px.max(x) + r, self.cols) + 1,
+            dim(py.min(y) - r, self.rows)..dim(py.max(y) + r, self.rows) + 1,
+        )
     }
 
-    pub fn insert_missile(&mut self, missile: &'a Missile) {
-        self.insert(Body::Missile(missile));
+    fn insert<T: Circle>(
+        &mut self,
+        circle: &T,
+        index: usize,
+        list: fn(&mut Cell) -> &mut Vec<u32>,
+    ) {
+        let (cols, rows) = self.span(circle);
+        for row in rows {
+            for col in cols.clone() {
+                let i = row * self.cols + col;
+                let cell = &mut self.cells[i];
+                if cell.is_empty() {
+                    self.occupied.push(i);
+                }
+                list(cell).push(index as u32);
+            }
+        }
     }
 
-    pub fn insert_enemy(&mut self, enemy: &'a Enemy) {
-        self.insert(Body::Enemy(enemy));
+    pub fn insert_missile(&mut self, missile: &Missile) {
+        let body = Body::of(missile.id, missile);
+        self.insert(&body, self.missiles.len(), |c| &mut c.missiles);
+        self.missiles.push(body);
     }
 
-    pub fn insert_ship(&mut self, ship: &'a Ship) {
-        self.insert(Body::Ship(ship));
+    pub fn insert_enemy(&mut self, enemy: &Enemy) {
+        let body = Body::of(enemy.id(), enemy);
+        self.insert(&body, self.enemies.len(), |c| &mut c.enemies);
+        self.enemies.push(body);
     }
 
-    pub fn spaces(&self) -> Values<(i64, i64), Space<'a>> {
-        self.bodies.values()
+    pub fn insert_power_up(&mut self, power_up: &PowerUp) {
+        let body = Body::of(power_up.id, power_up);
+        self.insert(&body, self.power_ups.len(), |c| &mut c.power_ups);
+        self.power_ups.push(body);
     }
 
-    fn insert(&mut self, body: Body<'a>) {
-        let b = match body {
-            Body::Missile(m) => to_rect(m),
-            Body::Enemy(e) => to_rect(e),
-            Body::Ship(s) => to_rect(s),
+    pub fn insert_ship(&mut self, ship: &Ship) {
+        if ship.is_dead() {
+            return;
+        }
+
+        let arena = self.arena;
+        let ((px, py), (x, y)) = (ship.prev_center(), ship.center());
+        let r = Circle::radius(ship);
+        // no shift, and one across each edge the ship crosses on its way
+        let shifts = |prev: f64, c: f64, limit: f64| {
+            [
+                Some(0.),
+                (prev.min(c) - r < 0.).then_some(limit),
+                (prev.max(c) + r > limit).then_some(-limit),
+            ]
         };
-        let (min_x, max_x) = self.hash((b.min_x, b.max_x));
-        let (min_y, max_y) = self.hash((b.min_y, b.max_y));
-
-        (min_x..(max_x + 1))
-            .flat_map(|x| {
-                (min_y..(max_y + 1))
-                    .map(|y| (x, y))
-                    .collect::<Vec<(i64, i64)>>()
-            })
-            .for_each(|k| {
-                let space = self.bodies.entry(k).or_default();
-                match body {
-                    Body::Missile(m) => (*space).missiles.push(m),
-                    Body::Enemy(e) => (*space).enemies.push(e),
-                    // TODO: avoid cloning?
-                    Body::Ship(s) => (*space).ships.push(s),
+
+        for &dx in shifts(px, x, arena.width).iter().flatten() {
+            for &dy in shifts(py, y, arena.height).iter().flatten() {
+                let body = Body {
+                    center: (x + dx, y + dy),
+                    prev_center: (px + dx, py + dy),
+                    ..Body::of(ship.id.clone(), ship)
                 };
-            })
+                self.insert(&body, self.ships.len(), |c| &mut c.ships);
+                self.ships.push(body);
+            }
+        }
+    }
+
+    // Narrow phase. Pairs that share more than one cell are reported once, in a stable order so
+    // that replays match.
+    pub fn collide(&mut self) {
+        for &i in &self.occupied {
+            let cell = &self.cells[i];
+            for &e in &cell.enemies {
+                let enemy = &self.enemies[e as usize];
+                for &m in &cell.missiles {
+                    let missile = &self.missiles[m as usize];
+                    if collided(enemy, missile) {
+                        self.enemy_missile_collisions.push((enemy.key, missile.key));
+                    }
+                }
+                for &s in &cell.ships {
+                    let ship = &self.ships[s as usize];
+                    if collided(ship, enemy) {
+                        // TODO: avoid cloning
+                        self.ship_enemy_collisions
+                            .push((ship.key.clone(), enemy.key));
+                    }
+                }
+            }
+            for &s in &cell.ships {
+                let ship = &self.ships[s as usize];
+                for &m in &cell.missiles {
+                    let missile = &self.missiles[m as usize];
+                    if collided(ship, missile) {
+                        self.ship_missile_collisions
+                            .push((ship.key.clone(), missile.key));
+                    }
+                }
+                for &p in &cell.power_ups {
+                    let power_up = &self.power_ups[p as usize];
+                    if collided(ship, power_up) {
+                        self.ship_power_up_collisions
+                            .push((ship.key.clone(), power_up.key));
+                    }
+                }
+            }
+            for (i, &s1) in cell.ships.iter().enumerate() {
+                let ship1 = &self.ships[s1 as usize];
+                for &s2 in &cell.ships[i + 1..] {
+                    let ship2 = &self.ships[s2 as usize];
+                    // a ship drawn on both sides of an edge does not bump into itself
+                    if ship1.key != ship2.key && collided(ship1, ship2) {
+                        let (a, b) = (ship1.key.clone(), ship2.key.clone());
+                        self.ship_ship_collisions
+                            .push(if a < b { (a, b) } else { (b, a) });
+                    }
+                }
+            }
+        }
+
+        self.enemy_missile_collisions.sort_unstable();
+        self.enemy_missile_collisions.dedup();
+        self.ship_enemy_collisions.sort_unstable();
+        self.ship_enemy_collisions.dedup();
+        self.ship_ship_collisions.sort_unstable();
+        self.ship_ship_collisions.dedup();
+        self.ship_missile_collisions.sort_unstable();
+        self.ship_missile_collisions.dedup();
+        self.ship_power_up_collisions.sort_unstable();
+        self.ship_power_up_collisions.dedup();
+    }
+
+    pub fn enemy_missile_collisions(&self) -> &[EnemyMissileCollision] {
+        &self.enemy_missile_collisions
+    }
+
+    pub fn ship_enemy_collisions(&self) -> &[ShipEnemyCollision] {
+        &self.ship_enemy_collisions
+    }
+
+    pub fn ship_ship_collisions(&self) -> &[ShipShipCollision] {
+        &self.ship_ship_collisions
+    }
+
+    pub fn ship_missile_collisions(&self) -> &[ShipMissileCollision] {
+        &self.ship_missile_collisions
+    }
+
+    pub fn ship_power_up_collisions(&self) -> &[ShipPowerUpCollision] {
+        &self.ship_power_up_collisions
     }
 }
 
 pub type ShipEnemyCollision = (ship::ID, enemy::ID);
 
+// The smaller ID comes first.
+pub type ShipShipCollision = (ship::ID, ship::ID);
+
+pub type ShipMissileCollision = (ship::ID, missile::ID);
+
+pub type ShipPowerUpCollision = (ship::ID, power_up::ID);
+
 pub type EnemyMissileCollision = (enemy::ID, missile::ID);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enemy::Enemies;
+    use crate::enemy::{bouncer, rock};
+    use crate::missile::Missiles;
+    use crate::rng::Pcg32;
+    use crate::ship::Ships;
+    use crate::weapon::Weapon;
+    use proptest::prelude::*;
+
+    struct World {
+        arena: Arena,
+        missiles: Vec<Missile>,
+        enemies: Vec<Enemy>,
+        ships: Ships,
+        power_ups: Vec<PowerUp>,
+    }
+
+    // Positions are top-left corners. Ships only ever sit where wrapping leaves them.
+    fn world(
+        seed: u64,
+        missiles: &[(f64, f64)],
+        enemies: &[(f64, f64, bool)],
+        ships: &[(f64, f64)],
+        power_ups: &[(f64, f64)],
+    ) -> World {
+        let mut rng = Pcg32::seed(seed);
+        let arena = Arena::init(None, None).unwrap();
+
+        let missiles = missiles
+            .iter()
+            .map(|&(x, y)| {
+                Missile::spawn("ship-0".to_string(), x, y, 0., Weapon::Single, &mut rng, 0)
+            })
+            .collect();
+        let enemies = enemies
+            .iter()
+            .map(|&(x, y, is_rock)| {
+                let mut enemy = if is_rock {
+                    Enemy::Rock(rock::spawn(Some(1.5), None, None, &arena, &mut rng, 0))
+                } else {
+                    Enemy::Bouncer(bouncer::spawn(None, None, &arena, &mut rng, 0))
+                };
+                enemy.place(x, y);
+                enemy
+            })
+            .collect();
+        let mut all = Ships::init();
+        for (i, &(x, y)) in ships.iter().enumerate() {
+            let id = format!("ship-{}", i);
+            all.spawn(id.clone(), None, None, 0);
+            all.place(&id, x, y, 0.).unwrap();
+        }
+
+        let power_ups = power_ups
+            .iter()
+            .map(|&(x, y)| PowerUp::spawn(power_up::Kind::Shield, x, y, &mut rng, 0))
+            .collect();
+
+        World {
+            arena,
+            missiles,
+            enemies,
+            ships: all,
+            power_ups,
+        }
+    }
+
+    fn grid(world: &World, cell_size: f64) -> Grid {
+        let mut grid = Grid::init(world.arena, Some(cell_size)).unwrap();
+        fill(&mut grid, world);
+        grid
+    }
+
+    fn fill(grid: &mut Grid, world: &World) {
+        world.missiles.iter().for_each(|m| grid.insert_missile(m));
+        world.enemies.iter().for_each(|e| grid.insert_enemy(e));
+        world.ships.entities().for_each(|s| grid.insert_ship(s));
+        world.power_ups.iter().for_each(|p| grid.insert_power_up(p));
+        grid.collide();
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Collisions {
+        enemy_missile: Vec<EnemyMissileCollision>,
+        ship_enemy: Vec<ShipEnemyCollision>,
+        ship_ship: Vec<ShipShipCollision>,
+        ship_missile: Vec<ShipMissileCollision>,
+        ship_power_up: Vec<ShipPowerUpCollision>,
+    }
+
+    impl Collisions {
+        fn of(grid: &Grid) -> Self {
+            Self {
+                enemy_missile: grid.enemy_missile_collisions().to_vec(),
+                ship_enemy: grid.ship_enemy_collisions().to_vec(),
+                ship_ship: grid.ship_ship_collisions().to_vec(),
+                ship_missile: grid.ship_missile_collisions().to_vec(),
+                ship_power_up: grid.ship_power_up_collisions().to_vec(),
+            }
+        }
+    }
+
+    // Every pair checked one by one, with ships also shifted by the arena size wherever the
+    // shifted copy still shows in the arena.
+    fn brute_force(world: &World) -> Collisions {
+        let mut all = Collisions::default();
+        let (width, height) = (world.arena.width, world.arena.height);
+        let ship_hits = |ship: &Ship, circle: &dyn Circle| {
+            let (x, y) = ship.center();
+            let r = Circle::radius(ship);
+            let shows = |c: f64, limit: f64| c - r < limit && c + r > 0.;
+            [-width, 0., width].iter().any(|dx| {
+                [-height, 0., height].iter().any(|dy| {
+                    let center = (x + dx, y + dy);
+                    shows(center.0, width)
+                        && shows(center.1, height)
+                        && distance(center, circle.center()) <= r + circle.radius()
+                })
+            })
+        };
+
+        for ship1 in world.ships.entities() {
+            for ship2 in world.ships.entities().filter(|s| s.id > ship1.id) {
+                // centers stay in the arena, so ships only ever touch the nearest copy of another
+                let (x, y) = ship1.center();
+                let hit = [-width, 0., width].iter().any(|dx| {
+                    [-height, 0., height].iter().any(|dy| {
+                        distance((x + dx, y + dy), ship2.center())
+                            <= Circle::radius(ship1) + Circle::radius(ship2)
+                    })
+                });
+                if hit {
+                    all.ship_ship.push((ship1.id.clone(), ship2.id.clone()));
+                }
+            }
+            for missile in world.missiles.iter().filter(|m| ship_hits(ship1, *m)) {
+                all.ship_missile.push((ship1.id.clone(), missile.id));
+            }
+            for power_up in world.power_ups.iter().filter(|p| ship_hits(ship1, *p)) {
+                all.ship_power_up.push((ship1.id.clone(), power_up.id));
+            }
+        }
+        for enemy in &world.enemies {
+            for missile in world.missiles.iter().filter(|m| collided(enemy, *m)) {
+                all.enemy_missile.push((enemy.id(), missile.id));
+            }
+            for ship in world.ships.entities().filter(|s| ship_hits(s, enemy)) {
+                all.ship_enemy.push((ship.id.clone(), enemy.id()));
+            }
+        }
+        all.enemy_missile.sort_unstable();
+        all.ship_enemy.sort_unstable();
+        all.ship_ship.sort_unstable();
+        all.ship_missile.sort_unstable();
+        all.ship_power_up.sort_unstable();
+        all
+    }
+
+    #[test]
+    fn ships_collide_across_the_edges() {
+        // a ship half across the right edge and a rock at the left edge
+        let edge = world(1, &[], &[(-20., 200., true)], &[(610., 200.)], &[]);
+        assert_eq!(
+            grid(&edge, DEFAULT_CELL_SIZE).ship_enemy_collisions().len(),
+            1
+        );
+
+        // and one across the corner
+        let corner = world(1, &[], &[(-20., -20., true)], &[(610., 450.)], &[]);
+        assert_eq!(
+            grid(&corner, DEFAULT_CELL_SIZE)
+                .ship_enemy_collisions()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn ships_meet_everything_across_the_edges() {
+        // two ships nose to nose across the left and right edges
+        let ships = world(1, &[], &[], &[(590., 200.), (-40., 200.)], &[]);
+        assert_eq!(
+            grid(&ships, DEFAULT_CELL_SIZE).ship_ship_collisions(),
+            [("ship-0".to_string(), "ship-1".to_string())]
+        );
+
+        // a ship across the bottom edge and a missile at the top
+        let missile = world(1, &[(343., 2.)], &[], &[(300., 430.)], &[]);
+        assert_eq!(
+            grid(&missile, DEFAULT_CELL_SIZE)
+                .ship_missile_collisions()
+                .len(),
+            1
+        );
+
+        // a ship in the bottom right corner and a power-up in the top left one, but not one on
+        // the far side of the left edge
+        let power_ups = world(1, &[], &[], &[(590., 430.)], &[(0., 0.), (0., 200.)]);
+        assert_eq!(
+            grid(&power_ups, DEFAULT_CELL_SIZE).ship_power_up_collisions(),
+            [("ship-0".to_string(), power_ups.power_ups[0].id)]
+        );
+    }
+
+    // A missile fired along the x axis, and the smallest rock centered `ahead` pixels in front of
+    // it and `aside` pixels off its path, after the missile flew for `dt` milliseconds.
+    fn missile_past_rock(ahead: f64, aside: f64, dt: i64) -> World {
+        let mut rng = Pcg32::seed(1);
+        let arena = Arena::init(None, None).unwrap();
+        let mut missiles = Missiles::init();
+        missiles.add(Missile::spawn(
+            "ship-0".to_string(),
+            100.,
+            100.,
+            0.,
+            Weapon::Single,
+            &mut rng,
+            0,
+        ));
+        let (x, y) = missiles.entities[0].center();
+
+        let mut rock = Enemy::Rock(rock::spawn(
+            Some(rock::MIN_SCALE),
+            None,
+            None,
+            &arena,
+            &mut rng,
+            0,
+        ));
+        let r = rock.radius();
+        rock.place(x + ahead - r, y + aside - r);
+        missiles.update(&Enemies::init(), dt);
+
+        World {
+            arena,
+            missiles: missiles.entities,
+            enemies: vec![rock],
+            ships: Ships::init(),
+            power_ups: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn fast_missiles_hit_what_they_fly_through() {
+        // 50 pixels in a tick, with the rock in between where the missile started and ended up
+        let through = missile_past_rock(25., 0., 100);
+        assert_eq!(
+            grid(&through, DEFAULT_CELL_SIZE)
+                .enemy_missile_collisions()
+                .len(),
+            1
+        );
+
+        let beside = missile_past_rock(25., 30., 100);
+        assert!(grid(&beside, DEFAULT_CELL_SIZE)
+            .enemy_missile_collisions()
+            .is_empty());
+
+        let short = missile_past_rock(100., 0., 100);
+        assert!(grid(&short, DEFAULT_CELL_SIZE)
+            .enemy_missile_collisions()
+            .is_empty());
+    }
+
+    #[test]
+    fn ships_do_not_sweep_across_the_arena_when_they_wrap() {
+        // a ship that crosses the right edge, and a rock on its row in the middle of the arena
+        let mut edge = world(1, &[], &[(300., 200., true)], &[(590., 200.)], &[]);
+        let id = "ship-0".to_string();
+        for _ in 0..10 {
+            edge.ships.thrust(&id, 0).unwrap();
+        }
+        edge.ships.update(&edge.arena, 100);
+        assert!(edge.ships.entities().all(|s| s.center().0 < 320.));
+
+        assert!(grid(&edge, DEFAULT_CELL_SIZE)
+            .ship_enemy_collisions()
+            .is_empty());
+    }
+
+    #[test]
+    fn init_rejects_grids_that_are_too_fine() {
+        let arena = Arena::init(Some(8000.), Some(8000.)).unwrap();
+        assert!(Grid::init(arena, None).is_ok());
+        assert!(Grid::init(arena, Some(10.)).is_err());
+        assert!(Grid::init(arena, Some(0.)).is_err());
+        assert!(Grid::init(arena, Some(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn clearing_forgets_everything() {
+        let world = world(
+            1,
+            &[(120., 120.)],
+            &[(100., 100., false)],
+            &[(90., 90.)],
+            &[],
+        );
+        let mut grid = grid(&world, DEFAULT_CELL_SIZE);
+        assert!(!grid.enemy_missile_collisions().is_empty());
+
+        grid.clear();
+        grid.collide();
+        assert!(grid.enemy_missile_collisions().is_empty());
+        assert!(grid.ship_enemy_collisions().is_empty());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(128))]
+
+        #[test]
+        fn grid_finds_every_overlap_once(
+            seed in any::<u64>(),
+            cell_size in 10. ..300f64,
+            missiles in prop::collection::vec((-150. ..750f64, -150. ..600f64), 0..40),
+            enemies in prop::collection::vec((-150. ..750f64, -150. ..600f64, any::<bool>()), 0..40),
+            ships in prop::collection::vec((-45. ..595f64, -45. ..435f64), 0..6),
+            power_ups in prop::collection::vec((-15. ..625f64, -15. ..465f64), 0..10),
+        ) {
+            let world = world(seed, &missiles, &enemies, &ships, &power_ups);
+            let grid = grid(&world, cell_size);
+            prop_assert_eq!(Collisions::of(&grid), brute_force(&world));
+        }
+
+        // the state keeps one grid for good, so whatever a tick leaves in it must not show up in
+        // the next one
+        #[test]
+        fn reused_grids_match_fresh_ones(
+            seeds in (any::<u64>(), any::<u64>()),
+            cell_size in 10. ..300f64,
+            missiles in prop::collection::vec((-150. ..750f64, -150. ..600f64), 0..40),
+            enemies in prop::collection::vec((-150. ..750f64, -150. ..600f64, any::<bool>()), 0..40),
+            ships in prop::collection::vec((-45. ..595f64, -45. ..435f64), 0..6),
+        ) {
+            let before = world(seeds.0, &missiles, &enemies, &ships, &[]);
+            // missiles moved and half the enemies gone
+            let mirrored: Vec<_> = missiles.iter().map(|&(x, y)| (640. - x, y)).collect();
+            let after = world(seeds.1, &mirrored, &enemies[enemies.len() / 2..], &ships, &[]);
+
+            let mut reused = grid(&before, cell_size);
+            reused.clear();
+            fill(&mut reused, &after);
+            prop_assert_eq!(Collisions::of(&reused), Collisions::of(&grid(&after, cell_size)));
+            prop_assert_eq!(Collisions::of(&reused), brute_force(&after));
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use crate::arena::Arena;
+use crate::error::Error;
+
+// Options a state is initialized with. Recordings keep them so a replay starts from the same
+// place. The default leaves every option absent.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "nif", derive(rustler::NifMap))]
+pub struct Config {
+    // a random seed is picked when absent
+    pub seed: Option<u64>,
+    // TOML level document, see `levels.toml`; the default progression when absent
+    pub levels: Option<String>,
+    // arena size in pixels, 640 by 480 when absent
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    // collision grid cell size in pixels, 50 when absent
+    pub cell_size: Option<f64>,
+    // how much of their closing speed ships keep when they bump into each other, from 0 to 1 for
+    // a fully elastic bounce; ships pass through each other when absent
+    pub ship_bounce: Option<f64>,
+    // whether missiles hit the ships of other players, off when absent
+    pub pvp: Option<bool>,
+    // whether missiles hit the ships of teammates too when they hit other players, off when absent
+    pub friendly_fire: Option<bool>,
+    // how likely a destroyed enemy is to leave a power-up behind, from 0 to 1; 0.1 when absent
+    pub power_up_chance: Option<f64>,
+}
+
+const DEFAULT_POWER_UP_CHANCE: f64 = 0.1;
+
+impl Config {
+    pub fn arena(&self) -> Result<Arena, Error> {
+        Arena::init(self.width, self.height)
+    }
+
+    pub fn ship_bounce(&self) -> Result<Option<f64>, Error> {
+        match self.ship_bounce {
+            Some(bounce) if !(0. ..=1.).contains(&bounce) => Err(Error::InvalidShipBounce(bounce)),
+            bounce => Ok(bounce),
+        }
+    }
+
+    pub fn power_up_chance(&self) -> Result<f64, Error> {
+        match self.power_up_chance.unwrap_or(DEFAULT_POWER_UP_CHANCE) {
+            chance if (0. ..=1.).contains(&chance) => Ok(chance),
+            chance => Err(Error::InvalidPowerUpChance(chance)),
+        }
+    }
+
+    // Pin the seed, picking a random one if none was given.
+    pub fn seeded(self) -> Self {
+        Self {
+            seed: Some(self.seed.unwrap_or_else(rand::random)),
+            ..self
+        }
+    }
+}
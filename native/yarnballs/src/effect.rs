@@ -0,0 +1,164 @@
+// Effects that last a while on a ship, from power-ups or from respawning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "nif", derive(rustler::NifUnitEnum))]
+pub enum Kind {
+    // enemies do no damage
+    Shield,
+    // every missile fired comes with one more on either side
+    TripleShot,
+    // thrusting accelerates faster
+    SpeedBoost,
+    // points scored count double, triple and so on with every stack
+    ScoreMultiplier,
+    // nothing does damage, for a short while after respawning
+    Invulnerable,
+    // thrusting accelerates slower with every stack, left behind by bouncers that hit the ship
+    Slow,
+}
+
+// What getting an effect that is on already does.
+pub enum Stacking {
+    // its time starts over
+    Refresh,
+    // its time is added to what is left, up to `MAX_EXTENSION` durations
+    Extend,
+    // it grows stronger, up to the given number of stacks, and its time starts over
+    Stack(u8),
+}
+
+const MAX_EXTENSION: i64 = 3;
+
+impl Kind {
+    // in milliseconds
+    pub fn duration(self) -> i64 {
+        match self {
+            Self::Shield => 8_000,
+            Self::TripleShot => 10_000,
+            Self::SpeedBoost => 10_000,
+            Self::ScoreMultiplier => 15_000,
+            Self::Invulnerable => 3_000,
+            Self::Slow => 4_000,
+        }
+    }
+
+    pub fn stacking(self) -> Stacking {
+        match self {
+            Self::Shield | Self::Invulnerable => Stacking::Refresh,
+            Self::TripleShot | Self::SpeedBoost => Stacking::Extend,
+            Self::ScoreMultiplier | Self::Slow => Stacking::Stack(3),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.Effect")]
+pub struct Effect {
+    pub kind: Kind,
+    // simulation time at which it wears off
+    pub until: i64,
+    // how many times it was added on top of itself, for effects that stack
+    pub stacks: u8,
+}
+
+// The effects on a ship, at most one of each kind.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.Effects")]
+pub struct Effects {
+    entities: Vec<Effect>,
+}
+
+impl Effects {
+    pub fn add(&mut self, kind: Kind, now: i64) {
+        let duration = kind.duration();
+        let Some(effect) = self.entities.iter_mut().find(|e| e.kind == kind) else {
+            self.entities.push(Effect {
+                kind,
+                until: now + duration,
+                stacks: 1,
+            });
+            return;
+        };
+        match kind.stacking() {
+            Stacking::Refresh => effect.until = now + duration,
+            Stacking::Extend => {
+                effect.until = (effect.until + duration).min(now + MAX_EXTENSION * duration)
+            }
+            Stacking::Stack(max) => {
+                effect.stacks = (effect.stacks + 1).min(max);
+                effect.until = now + duration;
+            }
+        }
+    }
+
+    pub fn has(&self, kind: Kind) -> bool {
+        self.stacks(kind) > 0
+    }
+
+    // 0 when the effect is off.
+    pub fn stacks(&self, kind: Kind) -> u8 {
+        self.entities
+            .iter()
+            .find(|e| e.kind == kind)
+            .map_or(0, |e| e.stacks)
+    }
+
+    // Effects wear off as their time runs out.
+    pub fn update(&mut self, now: i64) {
+        self.entities.retain(|e| e.until > now);
+    }
+
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+
+    pub fn entities(&self) -> &[Effect] {
+        &self.entities
+    }
+
+    // One bit per kind that is on, in the order kinds are declared.
+    pub fn mask(&self) -> u8 {
+        self.entities
+            .iter()
+            .fold(0, |mask, e| mask | 1 << e.kind as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn added(kind: Kind, times: &[i64]) -> Effect {
+        let mut effects = Effects::default();
+        times.iter().for_each(|&now| effects.add(kind, now));
+        effects.entities()[0].clone()
+    }
+
+    #[test]
+    fn effects_stack_by_their_own_rules() {
+        let shield = Kind::Shield.duration();
+        assert_eq!(added(Kind::Shield, &[0, 1000]).until, 1000 + shield);
+
+        let boost = Kind::SpeedBoost.duration();
+        assert_eq!(added(Kind::SpeedBoost, &[0, 1000]).until, 2 * boost);
+        assert_eq!(
+            added(Kind::SpeedBoost, &[0; 5]).until,
+            MAX_EXTENSION * boost
+        );
+
+        let slow = added(Kind::Slow, &[0, 1000, 2000, 3000]);
+        assert_eq!((slow.stacks, slow.until), (3, 3000 + Kind::Slow.duration()));
+    }
+
+    #[test]
+    fn effects_wear_off() {
+        let mut effects = Effects::default();
+        effects.add(Kind::Shield, 0);
+        effects.add(Kind::Invulnerable, 0);
+        effects.update(Kind::Invulnerable.duration());
+        assert!(effects.has(Kind::Shield));
+        assert!(!effects.has(Kind::Invulnerable));
+        assert_eq!(effects.stacks(Kind::Invulnerable), 0);
+    }
+}
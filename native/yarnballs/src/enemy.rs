@@ -1,20 +1,25 @@
 pub mod bouncer;
 pub mod rock;
 
-use crate::collision;
-use crate::explosion::{Explosion, Explosions};
-use crate::spawn;
-use crate::utils;
-use rand::{seq::SliceRandom, thread_rng};
-use rustler::{NifStruct, NifUntaggedEnum};
+use crate::arena::Arena;
+use crate::collision::{self, Circle};
+use crate::effect;
+use crate::explosion::{self, Explosion, Explosions};
+use crate::rng::Pcg32;
+use crate::snapshot::{Section, Writer};
+use crate::spawn::{self, Spawner};
+use rand::seq::SliceRandom;
 use std::collections::HashMap;
 
 pub type ID = (u64, u64);
 
-#[derive(NifStruct)]
-#[module = "Yarnballs.Enemies"]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.Enemies")]
 pub struct Enemies {
+    // only read by clients
+    #[cfg_attr(not(feature = "nif"), allow(dead_code))]
     spawned_count: i64,
+    #[cfg_attr(not(feature = "nif"), allow(dead_code))]
     destroyed_count: i64,
     pub entities: HashMap<ID, Enemy>,
     explosions: Explosions,
@@ -35,31 +40,41 @@ impl Enemies {
         }
     }
 
-    pub fn spawn(&mut self, spawners: Vec<Box<dyn spawn::Spawner>>) {
-        let last_spawned_at = utils::now_in_millis();
-        let dt = last_spawned_at - self.last_spawned_at;
+    pub fn spawn(&mut self, spawners: &[spawn::Config], arena: &Arena, rng: &mut Pcg32, now: i64) {
+        let dt = now - self.last_spawned_at;
 
-        let mut rng = thread_rng();
-        match spawners.choose(&mut rng) {
-            None => (),
-            Some(spawner) => {
-                if self.count() >= spawner.limit() || (dt as u32) <= spawner.interval() {
-                    return;
-                }
-
-                let enemy = spawner.spawn();
-                self.entities.insert(enemy.id(), enemy);
-                self.last_spawned_at = last_spawned_at;
+        if let Some(spawner) = spawners.choose(rng) {
+            if self.count() >= spawner.limit() || dt <= spawner.interval() as i64 {
+                return;
             }
+
+            let enemy = spawner.spawn(arena, rng, now);
+            self.entities.insert(enemy.id(), enemy);
+            self.last_spawned_at = now;
         }
     }
 
+    pub fn insert(&mut self, enemy: Enemy) {
+        self.entities.insert(enemy.id(), enemy);
+    }
+
     pub fn count(&self) -> u32 {
         self.entities.len() as u32
     }
 
-    pub fn entities(&self) -> Vec<&Enemy> {
-        self.entities.values().collect()
+    pub fn entities(&self) -> impl Iterator<Item = &Enemy> {
+        self.entities.values()
+    }
+
+    pub fn explosions(&self) -> &Explosions {
+        &self.explosions
+    }
+
+    pub fn frame(&self, enemies: &mut Section<ID>, explosions: &mut Section<explosion::ID>) {
+        self.entities
+            .values()
+            .for_each(|e| enemies.insert(e.id(), |w| e.write(w)));
+        self.explosions.frame(explosions);
     }
 
     pub fn remove(&mut self, ids: Vec<ID>) {
@@ -68,25 +83,28 @@ impl Enemies {
         }
     }
 
-    pub fn update(&mut self) {
-        self.explosions.update();
-        self.entities.retain(|_, e| !e.is_out_of_bounds());
-        self.entities.values_mut().for_each(|e| e.update());
+    // Returns where the enemies that were destroyed were, in a stable order.
+    pub fn update(&mut self, arena: &Arena, rng: &mut Pcg32, now: i64) -> Vec<(f64, f64)> {
+        self.explosions.update(now);
+        self.entities.retain(|_, e| !e.is_out_of_bounds(arena));
+        self.entities.values_mut().for_each(|e| e.update(now));
 
         let remove_ids = &self.remove_ids;
-        let to_explode: Vec<Enemy> = self
+        let mut to_explode: Vec<Enemy> = self
             .entities
             .values()
             .filter(|e| remove_ids.contains_key(&e.id()))
             // TODO: can we avoid cloning here?
             .cloned()
             .collect();
+        // splits and explosions draw from the rng, so visit them in a stable order
+        to_explode.sort_by_key(|e| e.id());
         let rock_splits: Vec<Enemy> = to_explode
             .iter()
             .filter(|e| remove_ids.contains_key(&e.id()))
             .flat_map(|e| match e {
                 Enemy::Bouncer(_) => Vec::new(),
-                Enemy::Rock(rock) => rock.split().into_iter().map(|r| Enemy::Rock(r)).collect(),
+                Enemy::Rock(rock) => rock.split(rng, now).into_iter().map(Enemy::Rock).collect(),
             })
             .collect();
         self.entities
@@ -95,18 +113,20 @@ impl Enemies {
             self.entities.insert(e.id(), e);
         });
         to_explode
-            .into_iter()
-            .for_each(|e| self.explosions.spawn(&e));
-        self.remove_ids.clear()
+            .iter()
+            .for_each(|e| self.explosions.spawn(e, rng, now));
+        self.remove_ids.clear();
+        to_explode.iter().map(|e| e.center()).collect()
     }
 
-    pub fn apply_missile_collisions(&mut self, emcs: &Vec<collision::EnemyMissileCollision>) {
-        let ids = emcs.into_iter().map(|(e, _)| *e).collect();
+    pub fn apply_missile_collisions(&mut self, emcs: &[collision::EnemyMissileCollision]) {
+        let ids = emcs.iter().map(|(e, _)| *e).collect();
         self.remove(ids);
     }
 }
 
-#[derive(NifUntaggedEnum, Clone)]
+#[derive(Clone)]
+#[cfg_attr(feature = "nif", derive(rustler::NifUntaggedEnum))]
 pub enum Enemy {
     Bouncer(bouncer::Bouncer),
     Rock(rock::Rock),
@@ -134,6 +154,22 @@ impl Enemy {
         }
     }
 
+    // Move the enemy without sweeping it across the arena on the next collision check.
+    pub fn place(&mut self, x: f64, y: f64) {
+        match self {
+            Self::Bouncer(b) => (b.x, b.y, b.prev_x, b.prev_y) = (x, y, x, y),
+            Self::Rock(r) => (r.x, r.y, r.prev_x, r.prev_y) = (x, y, x, y),
+        }
+    }
+
+    // Whether destroying the enemy leaves smaller ones behind.
+    pub fn splits(&self) -> bool {
+        match self {
+            Self::Bouncer(_) => false,
+            Self::Rock(rock) => rock.splits(),
+        }
+    }
+
     pub fn repel_vel(&self) -> f64 {
         match self {
             Self::Bouncer(_) => 1000.,
@@ -148,24 +184,45 @@ impl Enemy {
         }
     }
 
-    pub fn explode(&self) -> Explosion {
+    // What running into the enemy leaves on a ship, on top of the damage.
+    pub fn effect(&self) -> Option<effect::Kind> {
+        match self {
+            Self::Bouncer(_) => Some(effect::Kind::Slow),
+            Self::Rock(_) => None,
+        }
+    }
+
+    pub fn explode(&self, rng: &mut Pcg32, now: i64) -> Explosion {
+        match self {
+            Self::Bouncer(bouncer) => bouncer.explode(rng, now),
+            Self::Rock(rock) => rock.explode(rng, now),
+        }
+    }
+
+    pub fn is_out_of_bounds(&self, arena: &Arena) -> bool {
         match self {
-            Self::Bouncer(bouncer) => bouncer.explode(),
-            Self::Rock(rock) => rock.explode(),
+            Self::Bouncer(bouncer) => bouncer.is_out_of_bounds(arena),
+            Self::Rock(rock) => rock.is_out_of_bounds(arena),
         }
     }
 
-    pub fn is_out_of_bounds(&self) -> bool {
+    pub fn write(&self, w: &mut Writer) {
         match self {
-            Self::Bouncer(bouncer) => bouncer.is_out_of_bounds(),
-            Self::Rock(rock) => rock.is_out_of_bounds(),
+            Self::Bouncer(bouncer) => {
+                w.u8(0);
+                bouncer.write(w);
+            }
+            Self::Rock(rock) => {
+                w.u8(1);
+                rock.write(w);
+            }
         }
     }
 
-    pub fn update(&mut self) {
+    pub fn update(&mut self, now: i64) {
         match self {
-            Self::Bouncer(bouncer) => bouncer.update(),
-            Self::Rock(rock) => rock.update(),
+            Self::Bouncer(bouncer) => bouncer.update(now),
+            Self::Rock(rock) => rock.update(now),
         }
     }
 }
@@ -184,4 +241,11 @@ impl collision::Circle for Enemy {
             Self::Rock(rock) => rock.center(),
         }
     }
+
+    fn prev_center(&self) -> (f64, f64) {
+        match self {
+            Self::Bouncer(bouncer) => bouncer.prev_center(),
+            Self::Rock(rock) => rock.prev_center(),
+        }
+    }
 }
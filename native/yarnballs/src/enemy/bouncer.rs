@@ -1,17 +1,23 @@
+use crate::arena::Arena;
 use crate::enemy::ID;
+use crate::rng::Pcg32;
+use crate::snapshot::Writer;
 use crate::{collision, explosion::Explosion, utils};
-use rand::{seq::SliceRandom, thread_rng, Rng};
-use rustler::NifStruct;
+use rand::Rng;
 
 // Entity
 
-#[derive(NifStruct, Clone)]
-#[module = "Yarnballs.Enemy.Bouncer"]
+#[derive(Clone)]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.Enemy.Bouncer")]
 pub struct Bouncer {
     id: ID,
     updated_at: i64,
     pub x: f64,
     pub y: f64,
+    // where the enemy was at the start of the last tick
+    pub prev_x: f64,
+    pub prev_y: f64,
     vel_x: f64,
     vel_y: f64,
 }
@@ -21,68 +27,74 @@ impl Bouncer {
         self.id
     }
 
-    pub fn update(&mut self) {
-        let updated_at = utils::now_in_millis();
-        let dt = updated_at - self.updated_at;
+    pub fn write(&self, w: &mut Writer) {
+        w.id(self.id);
+        w.position(self.x, self.y);
+        w.velocity(self.vel_x, self.vel_y);
+    }
+
+    pub fn update(&mut self, now: i64) {
+        let dt = now - self.updated_at;
 
+        (self.prev_x, self.prev_y) = (self.x, self.y);
         self.x += self.vel_x * ((dt as f64) / 1000.0);
         self.y += self.vel_y * ((dt as f64) / 1000.0);
-        self.updated_at = updated_at;
+        self.updated_at = now;
     }
 
     const OUT_OF_BOUNDS_PADDING: f64 = 100.;
 
-    pub fn is_out_of_bounds(&self) -> bool {
-        self.x < -Bouncer::OUT_OF_BOUNDS_PADDING
-            || self.y < -Bouncer::OUT_OF_BOUNDS_PADDING
-            || self.x > utils::WIDTH + Bouncer::OUT_OF_BOUNDS_PADDING
-            || self.y > utils::HEIGHT + Bouncer::OUT_OF_BOUNDS_PADDING
+    pub fn is_out_of_bounds(&self, arena: &Arena) -> bool {
+        arena.is_outside(self.x, self.y, Bouncer::OUT_OF_BOUNDS_PADDING)
     }
 
-    pub fn explode(&self) -> Explosion {
-        Explosion::spawn(self.x, self.y, collision::Circle::radius(self) * 2.)
+    pub fn explode(&self, rng: &mut Pcg32, now: i64) -> Explosion {
+        Explosion::spawn(
+            self.x,
+            self.y,
+            collision::Circle::radius(self) * 2.,
+            rng,
+            now,
+        )
     }
 }
 
-const DEFAULT_MIN_VEL: f64 = 50.;
-const DEFAULT_MAX_VEL: f64 = 100.;
+pub const DEFAULT_MIN_VEL: f64 = 50.;
+pub const DEFAULT_MAX_VEL: f64 = 100.;
 
 const ARC: f64 = 50.;
 const PADDING: f64 = 50.;
 
-fn spawn_horizontal() -> (f64, f64) {
-    let mut rng = thread_rng();
-
-    let x = *[-PADDING, utils::WIDTH + PADDING].choose(&mut rng).unwrap();
-    let y = *[0.0, utils::HEIGHT].choose(&mut rng).unwrap();
+fn spawn_horizontal(arena: &Arena, rng: &mut Pcg32) -> (f64, f64) {
+    let x = utils::either(rng, -PADDING, arena.width + PADDING);
+    let y = utils::either(rng, 0.0, arena.height);
     (x, y)
 }
 
-fn spawn_vertical() -> (f64, f64) {
-    let mut rng = thread_rng();
-
-    let x = *[0.0, utils::WIDTH].choose(&mut rng).unwrap();
-    let y = *[-PADDING, utils::HEIGHT + PADDING]
-        .choose(&mut rng)
-        .unwrap();
+fn spawn_vertical(arena: &Arena, rng: &mut Pcg32) -> (f64, f64) {
+    let x = utils::either(rng, 0.0, arena.width);
+    let y = utils::either(rng, -PADDING, arena.height + PADDING);
     (x, y)
 }
 
-pub fn spawn(min_vel: Option<f64>, max_vel: Option<f64>) -> Bouncer {
+pub fn spawn(
+    min_vel: Option<f64>,
+    max_vel: Option<f64>,
+    arena: &Arena,
+    rng: &mut Pcg32,
+    now: i64,
+) -> Bouncer {
     let min_vel = min_vel.unwrap_or(DEFAULT_MIN_VEL);
     let max_vel = max_vel.unwrap_or(DEFAULT_MAX_VEL);
 
     // spawn vertical or horizontal
-    let mut rng = thread_rng();
-    let b = *[true, false].choose(&mut rng).unwrap();
-    let (spawn_x, spawn_y) = if b {
-        spawn_horizontal()
+    let (spawn_x, spawn_y) = if rng.gen_bool(0.5) {
+        spawn_horizontal(arena, rng)
     } else {
-        spawn_vertical()
+        spawn_vertical(arena, rng)
     };
 
-    let center_x = utils::WIDTH / 2.0;
-    let center_y = utils::HEIGHT / 2.0;
+    let (center_x, center_y) = arena.center();
 
     // random arc
     let angle_adjustment = rng.gen_range(-ARC..ARC).to_radians();
@@ -93,10 +105,12 @@ pub fn spawn(min_vel: Option<f64>, max_vel: Option<f64>) -> Bouncer {
     let vel_y = rng.gen_range(min_vel..max_vel) * angle.sin();
 
     Bouncer {
-        id: utils::new_uuid_as_u64_pair(),
-        updated_at: utils::now_in_millis(),
+        id: utils::new_uuid_as_u64_pair(rng),
+        updated_at: now,
         x: spawn_x,
         y: spawn_y,
+        prev_x: spawn_x,
+        prev_y: spawn_y,
         vel_x,
         vel_y,
     }
@@ -110,4 +124,8 @@ impl collision::Circle for Bouncer {
     fn center(&self) -> (f64, f64) {
         (self.x + self.radius(), self.y + self.radius())
     }
+
+    fn prev_center(&self) -> (f64, f64) {
+        (self.prev_x + self.radius(), self.prev_y + self.radius())
+    }
 }
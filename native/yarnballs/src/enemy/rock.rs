@@ -1,17 +1,23 @@
+use crate::arena::Arena;
 use crate::enemy::ID;
+use crate::rng::Pcg32;
+use crate::snapshot::Writer;
 use crate::{collision, explosion::Explosion, utils};
-use rand::{seq::SliceRandom, thread_rng, Rng};
-use rustler::NifStruct;
+use rand::Rng;
 
 // Entity
 
-#[derive(NifStruct, Clone)]
-#[module = "Yarnballs.Enemy.Rock"]
+#[derive(Clone)]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.Enemy.Rock")]
 pub struct Rock {
     id: ID,
     updated_at: i64,
     pub x: f64,
     pub y: f64,
+    // where the enemy was at the start of the last tick
+    pub prev_x: f64,
+    pub prev_y: f64,
     vel_x: f64,
     vel_y: f64,
     scale: f64,
@@ -19,9 +25,10 @@ pub struct Rock {
 
 const PADDING: f64 = 50.;
 const ARC: f64 = 50.;
-const DEFAULT_MIN_VEL: f64 = 50.;
-const DEFAULT_MAX_VEL: f64 = 100.;
-const DEFAULT_SCALE: f64 = 0.75;
+pub const DEFAULT_MIN_VEL: f64 = 50.;
+pub const DEFAULT_MAX_VEL: f64 = 100.;
+pub const DEFAULT_SCALE: f64 = 0.75;
+pub const MIN_SCALE: f64 = 0.3;
 const BASE_DAMAGE: f64 = 5.;
 
 impl Rock {
@@ -33,39 +40,51 @@ impl Rock {
         BASE_DAMAGE * self.scale
     }
 
-    pub fn update(&mut self) {
-        let updated_at = utils::now_in_millis();
-        let dt = updated_at - self.updated_at;
+    pub fn write(&self, w: &mut Writer) {
+        w.id(self.id);
+        w.position(self.x, self.y);
+        w.velocity(self.vel_x, self.vel_y);
+        w.u8((self.scale * 100.).round() as u8);
+    }
+
+    pub fn update(&mut self, now: i64) {
+        let dt = now - self.updated_at;
 
+        (self.prev_x, self.prev_y) = (self.x, self.y);
         self.x += self.vel_x * ((dt as f64) / 1000.0);
         self.y += self.vel_y * ((dt as f64) / 1000.0);
-        self.updated_at = updated_at;
+        self.updated_at = now;
     }
 
     const OUT_OF_BOUNDS_PADDING: f64 = 100.;
 
-    pub fn is_out_of_bounds(&self) -> bool {
-        self.x < -Rock::OUT_OF_BOUNDS_PADDING
-            || self.y < -Rock::OUT_OF_BOUNDS_PADDING
-            || self.x > utils::WIDTH + Rock::OUT_OF_BOUNDS_PADDING
-            || self.y > utils::HEIGHT + Rock::OUT_OF_BOUNDS_PADDING
+    pub fn is_out_of_bounds(&self, arena: &Arena) -> bool {
+        arena.is_outside(self.x, self.y, Rock::OUT_OF_BOUNDS_PADDING)
     }
 
-    pub fn explode(&self) -> Explosion {
-        Explosion::spawn(self.x, self.y, collision::Circle::radius(self) * 2.)
+    pub fn explode(&self, rng: &mut Pcg32, now: i64) -> Explosion {
+        Explosion::spawn(
+            self.x,
+            self.y,
+            collision::Circle::radius(self) * 2.,
+            rng,
+            now,
+        )
     }
 
     fn radius(&self) -> f64 {
         collision::Circle::radius(self)
     }
 
-    pub fn split(&self) -> Vec<Self> {
-        if self.scale <= DEFAULT_SCALE {
+    // Only rocks larger than the default split when destroyed.
+    pub fn splits(&self) -> bool {
+        self.scale > DEFAULT_SCALE
+    }
+
+    pub fn split(&self, rng: &mut Pcg32, now: i64) -> Vec<Self> {
+        if !self.splits() {
             Vec::new()
         } else {
-            // get RNG
-            let mut rng = thread_rng();
-
             // random angle
             let angle = rng.gen_range(1_f64..360_f64).to_radians();
 
@@ -81,6 +100,8 @@ impl Rock {
                         Some(self.scale / 2.),
                         None,
                         None,
+                        rng,
+                        now,
                     )
                 })
                 .collect()
@@ -88,44 +109,45 @@ impl Rock {
     }
 }
 
-pub fn spawn(max_scale: Option<f64>, min_vel: Option<f64>, max_vel: Option<f64>) -> Rock {
+pub fn spawn(
+    max_scale: Option<f64>,
+    min_vel: Option<f64>,
+    max_vel: Option<f64>,
+    arena: &Arena,
+    rng: &mut Pcg32,
+    now: i64,
+) -> Rock {
     // spawn vertical or horizontal
-    let mut rng = thread_rng();
-    let b = *[true, false].choose(&mut rng).unwrap();
-    let (spawn_x, spawn_y) = if b {
-        spawn_horizontal()
+    let (spawn_x, spawn_y) = if rng.gen_bool(0.5) {
+        spawn_horizontal(arena, rng)
     } else {
-        spawn_vertical()
+        spawn_vertical(arena, rng)
     };
 
-    let center_x = utils::WIDTH / 2.0;
-    let center_y = utils::HEIGHT / 2.0;
+    let (center_x, center_y) = arena.center();
 
     // random arc
     let angle_adjustment = rng.gen_range(-ARC..ARC).to_radians();
     let angle = (center_y - spawn_y).atan2(center_x - spawn_x) + angle_adjustment;
 
-    spawn_at(spawn_x, spawn_y, angle, max_scale, min_vel, max_vel)
+    spawn_at(
+        spawn_x, spawn_y, angle, max_scale, min_vel, max_vel, rng, now,
+    )
 }
 
-fn spawn_horizontal() -> (f64, f64) {
-    let mut rng = thread_rng();
-
-    let x = *[-PADDING, utils::WIDTH + PADDING].choose(&mut rng).unwrap();
-    let y = *[0.0, utils::HEIGHT].choose(&mut rng).unwrap();
+fn spawn_horizontal(arena: &Arena, rng: &mut Pcg32) -> (f64, f64) {
+    let x = utils::either(rng, -PADDING, arena.width + PADDING);
+    let y = utils::either(rng, 0.0, arena.height);
     (x, y)
 }
 
-fn spawn_vertical() -> (f64, f64) {
-    let mut rng = thread_rng();
-
-    let x = *[0.0, utils::WIDTH].choose(&mut rng).unwrap();
-    let y = *[-PADDING, utils::HEIGHT + PADDING]
-        .choose(&mut rng)
-        .unwrap();
+fn spawn_vertical(arena: &Arena, rng: &mut Pcg32) -> (f64, f64) {
+    let x = utils::either(rng, 0.0, arena.width);
+    let y = utils::either(rng, -PADDING, arena.height + PADDING);
     (x, y)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn spawn_at(
     x: f64,
     y: f64,
@@ -133,26 +155,27 @@ fn spawn_at(
     max_scale: Option<f64>,
     min_vel: Option<f64>,
     max_vel: Option<f64>,
+    rng: &mut Pcg32,
+    now: i64,
 ) -> Rock {
     let max_scale = max_scale.unwrap_or(DEFAULT_SCALE);
     let min_vel = min_vel.unwrap_or(DEFAULT_MIN_VEL);
     let max_vel = max_vel.unwrap_or(DEFAULT_MAX_VEL);
 
-    // get RNG
-    let mut rng = thread_rng();
-
     // random velocity
     let vel_x = rng.gen_range(min_vel..max_vel) * angle.cos();
     let vel_y = rng.gen_range(min_vel..max_vel) * angle.sin();
 
     // random scale
-    let scale = rng.gen_range(30.0..(max_scale * 100.)) / 100.;
+    let scale = rng.gen_range((MIN_SCALE * 100.)..=(max_scale * 100.)) / 100.;
 
     Rock {
-        id: utils::new_uuid_as_u64_pair(),
-        updated_at: utils::now_in_millis(),
+        id: utils::new_uuid_as_u64_pair(rng),
+        updated_at: now,
         x,
         y,
+        prev_x: x,
+        prev_y: y,
         vel_x,
         vel_y,
         scale,
@@ -167,4 +190,57 @@ impl collision::Circle for Rock {
     fn center(&self) -> (f64, f64) {
         (self.x + self.radius(), self.y + self.radius())
     }
+
+    fn prev_center(&self) -> (f64, f64) {
+        (self.prev_x + self.radius(), self.prev_y + self.radius())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    fn rock(scale: f64) -> Rock {
+        Rock {
+            id: (0, 0),
+            updated_at: 0,
+            x: 100.,
+            y: 100.,
+            prev_x: 100.,
+            prev_y: 100.,
+            vel_x: 0.,
+            vel_y: 0.,
+            scale,
+        }
+    }
+
+    #[test]
+    fn small_rocks_do_not_split() {
+        let mut rng = Pcg32::seed(1);
+        assert!(rock(MIN_SCALE).split(&mut rng, 0).is_empty());
+        assert!(rock(DEFAULT_SCALE).split(&mut rng, 0).is_empty());
+    }
+
+    proptest! {
+        #[test]
+        fn large_rocks_split_in_four_smaller_rocks(
+            scale in (DEFAULT_SCALE + 0.01)..3.,
+            seed in any::<u64>(),
+        ) {
+            let rock = rock(scale);
+            let pieces = rock.split(&mut Pcg32::seed(seed), 16);
+            prop_assert_eq!(pieces.len(), 4);
+
+            let ids: HashSet<ID> = pieces.iter().map(|p| p.id).collect();
+            prop_assert_eq!(ids.len(), 4);
+            for piece in &pieces {
+                prop_assert!(piece.scale >= MIN_SCALE && piece.scale <= scale / 2. + 0.01);
+                prop_assert_eq!(piece.updated_at, 16);
+                // pieces fly off from the center of the rock
+                prop_assert_eq!((piece.x, piece.y), (rock.x + rock.radius(), rock.y + rock.radius()));
+            }
+        }
+    }
 }
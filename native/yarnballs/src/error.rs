@@ -0,0 +1,37 @@
+use crate::level;
+use crate::ship;
+
+// Errors returned to Elixir as `{:error, reason}` instead of raising from inside a NIF.
+#[derive(Debug)]
+#[cfg_attr(feature = "nif", derive(rustler::NifTaggedEnum))]
+pub enum Error {
+    // `{:unknown_ship, id}`: a command named a ship that is not in the world
+    UnknownShip(ship::ID),
+    // `{:unknown_room, id}`: no world is registered under the room id
+    UnknownRoom(String),
+    // `{:room_taken, id}`: a world is registered under the room id already
+    RoomTaken(String),
+    // `{:invalid_config, reasons}`: the level document was rejected, see `level::Error`
+    InvalidConfig(Vec<level::Error>),
+    // `{:invalid_arena, %{width: w, height: h}}`: the arena is too small, too large or not a number
+    InvalidArena { width: f64, height: f64 },
+    // `{:invalid_cell_size, cell_size}`: the collision grid cell size is below 1 or too small for
+    // the arena
+    InvalidCellSize(f64),
+    // `{:invalid_ship_bounce, bounce}`: the ship bounce is not between 0 and 1
+    InvalidShipBounce(f64),
+    // `{:invalid_power_up_chance, chance}`: the power-up chance is not between 0 and 1
+    InvalidPowerUpChance(f64),
+    // `{:decode, argument}`: a NIF argument was not of the expected type
+    Decode(String),
+    // `:poisoned`: an earlier call panicked while holding the world
+    Poisoned,
+    // `:out_of_memory`: a binary could not be allocated
+    OutOfMemory,
+}
+
+impl From<Vec<level::Error>> for Error {
+    fn from(errors: Vec<level::Error>) -> Self {
+        Self::InvalidConfig(errors)
+    }
+}
@@ -0,0 +1,65 @@
+use crate::ship;
+use std::collections::VecDeque;
+
+// Things that happened in the world that clients announce rather than render.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "nif", derive(rustler::NifTaggedEnum))]
+pub enum Event {
+    // `{:kill, %{killer_id: id, victim_id: id, at: now}}`: a ship was shot down by another one
+    Kill {
+        killer_id: ship::ID,
+        victim_id: ship::ID,
+        at: i64,
+    },
+}
+
+// Keeps a world nobody drains events from from growing without bound.
+const MAX_EVENTS: usize = 256;
+
+// Events in the order they happened, kept until they are drained. The oldest are dropped once
+// there are too many.
+#[derive(Default)]
+pub struct Events {
+    entities: VecDeque<Event>,
+}
+
+impl Events {
+    pub fn init() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, event: Event) {
+        if self.entities.len() == MAX_EVENTS {
+            self.entities.pop_front();
+        }
+        self.entities.push_back(event);
+    }
+
+    pub fn drain(&mut self) -> Vec<Event> {
+        self.entities.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kill(at: i64) -> Event {
+        Event::Kill {
+            killer_id: "a".to_string(),
+            victim_id: "b".to_string(),
+            at,
+        }
+    }
+
+    #[test]
+    fn only_the_latest_events_are_kept() {
+        let mut events = Events::init();
+        (0..MAX_EVENTS as i64 + 10).for_each(|at| events.push(kill(at)));
+
+        let drained = events.drain();
+        assert_eq!(drained.len(), MAX_EVENTS);
+        assert_eq!(drained[0], kill(10));
+        assert!(events.drain().is_empty());
+    }
+}
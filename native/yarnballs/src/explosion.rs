@@ -1,9 +1,12 @@
 use crate::enemy::Enemy;
+use crate::rng::Pcg32;
+use crate::snapshot::{Section, Writer};
 use crate::utils;
-use rustler::NifStruct;
 
-#[derive(NifStruct)]
-#[module = "Yarnballs.Explosions"]
+pub type ID = (u64, u64);
+
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.Explosions")]
 pub struct Explosions {
     entities: Vec<Explosion>,
 }
@@ -15,21 +18,32 @@ impl Explosions {
         }
     }
 
-    pub fn spawn(&mut self, enemy: &Enemy) {
-        let explosion = enemy.explode();
+    pub fn spawn(&mut self, enemy: &Enemy, rng: &mut Pcg32, now: i64) {
+        let explosion = enemy.explode(rng, now);
         self.entities.push(explosion)
     }
 
-    pub fn update(&mut self) {
+    pub fn frame(&self, section: &mut Section<ID>) {
+        self.entities
+            .iter()
+            .for_each(|e| section.insert(e.id, |w| e.write(w)));
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = &Explosion> {
+        self.entities.iter()
+    }
+
+    pub fn update(&mut self, now: i64) {
         self.entities.retain(|e| e.lifespan > 0);
-        self.entities.iter_mut().for_each(|e| e.update());
+        self.entities.iter_mut().for_each(|e| e.update(now));
     }
 }
 
-#[derive(NifStruct)]
-#[module = "Yarnballs.Explosion"]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.Explosion")]
 pub struct Explosion {
-    id: String,
+    id: ID,
+    spawned_at: i64,
     updated_at: i64,
     x: f64,
     y: f64,
@@ -41,10 +55,15 @@ pub struct Explosion {
 const LIFESPAN: i64 = 1000;
 
 impl Explosion {
-    pub fn spawn(x: f64, y: f64, size: f64) -> Self {
+    pub fn position(&self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+
+    pub fn spawn(x: f64, y: f64, size: f64, rng: &mut Pcg32, now: i64) -> Self {
         Self {
-            id: utils::new_uuid(),
-            updated_at: utils::now_in_millis(),
+            id: utils::new_uuid_as_u64_pair(rng),
+            spawned_at: now,
+            updated_at: now,
             x,
             y,
             size,
@@ -52,11 +71,19 @@ impl Explosion {
         }
     }
 
-    pub fn update(&mut self) {
-        let updated_at = utils::now_in_millis();
-        let dt = updated_at - self.updated_at;
+    // Only what stays the same, so that explosions are sent once. Clients fade them out from
+    // when they spawned.
+    fn write(&self, w: &mut Writer) {
+        w.id(self.id);
+        w.position(self.x, self.y);
+        w.u16(self.size.round() as u16);
+        w.i64(self.spawned_at);
+    }
+
+    pub fn update(&mut self, now: i64) {
+        let dt = now - self.updated_at;
 
         self.lifespan -= dt;
-        self.updated_at = updated_at;
+        self.updated_at = now;
     }
 }
@@ -0,0 +1,240 @@
+use crate::enemy::rock;
+use crate::spawn;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Document {
+    levels: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    score: u32,
+    spawners: Vec<spawn::Config>,
+}
+
+#[derive(Clone)]
+pub struct Level {
+    pub index: u32,
+    // the total score at which this level starts
+    pub min_score: u32,
+    // the total score at which the next level starts, if any
+    pub max_score: Option<u32>,
+    pub spawners: Vec<spawn::Config>,
+}
+
+// Why a level document was rejected. Levels and spawners are numbered from 0 in document order.
+#[derive(Debug)]
+#[cfg_attr(feature = "nif", derive(rustler::NifTaggedEnum))]
+pub enum Error {
+    Parse(String),
+    NoLevels,
+    FirstScoreNotZero,
+    ScoreNotIncreasing { level: u32 },
+    NoSpawners { level: u32 },
+    InvalidVelocityRange { level: u32, spawner: u32 },
+    ScaleTooSmall { level: u32, spawner: u32 },
+}
+
+/*
+Parse and validate a TOML level document, see `levels.toml` for the format and the default
+progression.
+
+Levels are indexed in document order, and each one lasts until the next level's score.
+*/
+pub fn parse(document: &str) -> Result<Vec<Level>, Vec<Error>> {
+    let document: Document =
+        toml::from_str(document).map_err(|e| vec![Error::Parse(e.message().to_string())])?;
+    let next_scores: Vec<Option<u32>> = document
+        .levels
+        .iter()
+        .skip(1)
+        .map(|e| Some(e.score))
+        .chain([None])
+        .collect();
+
+    let levels: Vec<Level> = document
+        .levels
+        .into_iter()
+        .zip(next_scores)
+        .enumerate()
+        .map(|(index, (entry, max_score))| Level {
+            index: index as u32,
+            min_score: entry.score,
+            max_score,
+            spawners: entry.spawners,
+        })
+        .collect();
+
+    let errors = validate(&levels);
+    if errors.is_empty() {
+        Ok(levels)
+    } else {
+        Err(errors)
+    }
+}
+
+fn validate(levels: &[Level]) -> Vec<Error> {
+    let mut errors = Vec::new();
+
+    match levels.first() {
+        None => errors.push(Error::NoLevels),
+        Some(first) if first.min_score != 0 => errors.push(Error::FirstScoreNotZero),
+        Some(_) => (),
+    }
+
+    for level in levels {
+        if level.max_score.is_some_and(|max| max <= level.min_score) {
+            errors.push(Error::ScoreNotIncreasing {
+                level: level.index + 1,
+            });
+        }
+        if level.spawners.is_empty() {
+            errors.push(Error::NoSpawners { level: level.index });
+        }
+
+        for (spawner, config) in level.spawners.iter().enumerate() {
+            let (level, spawner) = (level.index, spawner as u32);
+
+            // spawning draws from these ranges, and an empty or unbounded range panics - so does
+            // one whose ends are finite but too far apart to measure
+            let (min_vel, max_vel) = config.vel_range();
+            if !(min_vel < max_vel && (max_vel - min_vel).is_finite()) {
+                errors.push(Error::InvalidVelocityRange { level, spawner });
+            }
+            if config
+                .max_scale()
+                .is_some_and(|max| !((max * 100.).is_finite() && max >= rock::MIN_SCALE))
+            {
+                errors.push(Error::ScaleTooSmall { level, spawner });
+            }
+        }
+    }
+
+    errors
+}
+
+pub fn defaults() -> Vec<Level> {
+    parse(include_str!("levels.toml")).expect("default levels must parse")
+}
+
+// The level reached at the given score.
+pub fn current(levels: &[Level], score: i64) -> &Level {
+    levels
+        .iter()
+        .rev()
+        .find(|l| score >= l.min_score as i64)
+        .unwrap_or(&levels[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::Arena;
+    use crate::rng::Pcg32;
+    use crate::spawn::Spawner;
+    use proptest::prelude::*;
+
+    fn errors(document: &str) -> Vec<Error> {
+        parse(document).err().unwrap_or_default()
+    }
+
+    // One `[[levels]]` table.
+    fn level(score: u32, spawners: &str) -> String {
+        format!("[[levels]]\nscore = {}\nspawners = [{}]\n", score, spawners)
+    }
+
+    #[test]
+    fn the_bundled_levels_parse() {
+        let levels = defaults();
+        assert!(!levels.is_empty());
+        assert_eq!(levels[0].min_score, 0);
+        assert_eq!(levels.last().unwrap().max_score, None);
+        for pair in levels.windows(2) {
+            assert_eq!(pair[0].max_score, Some(pair[1].min_score));
+        }
+        assert_eq!(current(&levels, 0).index, 0);
+        assert_eq!(current(&levels, i64::MAX).index as usize, levels.len() - 1);
+    }
+
+    #[test]
+    fn each_mistake_is_reported() {
+        let bouncers = r#"{ kind = "bouncers", limit = 5, interval = 1000 }"#;
+        let tiny_rocks = r#"{ kind = "rocks", limit = 5, interval = 1000, max_scale = 0.01 }"#;
+
+        assert!(matches!(errors("levels = 1")[..], [Error::Parse(_)]));
+        assert!(matches!(errors("levels = []")[..], [Error::NoLevels]));
+        assert!(matches!(
+            errors(&level(10, bouncers))[..],
+            [Error::FirstScoreNotZero]
+        ));
+        assert!(matches!(
+            errors(&[level(0, bouncers), level(20, bouncers), level(20, bouncers)].concat())[..],
+            [Error::ScoreNotIncreasing { level: 2 }]
+        ));
+        assert!(matches!(
+            errors(&level(0, ""))[..],
+            [Error::NoSpawners { level: 0 }]
+        ));
+        assert!(matches!(
+            errors(
+                &[
+                    level(0, bouncers),
+                    level(10, &[bouncers, tiny_rocks].join(", ")),
+                ]
+                .concat()
+            )[..],
+            [Error::ScaleTooSmall {
+                level: 1,
+                spawner: 1
+            }]
+        ));
+    }
+
+    #[test]
+    fn velocity_ranges_must_be_measurable() {
+        for (min_vel, max_vel) in [
+            ("100.0", "50.0"),
+            ("50.0", "50.0"),
+            ("nan", "50.0"),
+            ("50.0", "inf"),
+            ("-1e308", "1e308"),
+        ] {
+            let bouncers = format!(
+                r#"{{ kind = "bouncers", limit = 5, interval = 1000, min_vel = {}, max_vel = {} }}"#,
+                min_vel, max_vel
+            );
+            assert!(
+                matches!(
+                    errors(&level(0, &bouncers))[..],
+                    [Error::InvalidVelocityRange {
+                        level: 0,
+                        spawner: 0
+                    }]
+                ),
+                "{}..{}",
+                min_vel,
+                max_vel
+            );
+        }
+    }
+
+    proptest! {
+        // whatever a document asks for, if it is accepted it can be spawned from
+        #[test]
+        fn accepted_spawners_spawn(
+            min_vel in prop::num::f64::ANY,
+            max_vel in prop::num::f64::ANY,
+            max_scale in prop::num::f64::ANY,
+        ) {
+            let rocks = format!(
+                r#"{{ kind = "rocks", limit = 5, interval = 1000, min_vel = {:?}, max_vel = {:?}, max_scale = {:?} }}"#,
+                min_vel, max_vel, max_scale
+            );
+            if let Ok(levels) = parse(&level(0, &rocks)) {
+                let arena = Arena::init(None, None).unwrap();
+                levels[0].spawners[0].spawn(&arena, &mut Pcg32::seed(1), 0);
+            }
+        }
+    }
+}
@@ -1,13 +1,18 @@
+use crate::collision::Circle;
+use crate::enemy::Enemies;
+use crate::rng::Pcg32;
+use crate::snapshot::{Section, Writer};
+use crate::weapon::{Stats, Weapon};
 use crate::{collision, ship, utils};
-use rustler::NifStruct;
 use std::collections::{HashMap, HashSet};
+use std::f64::consts::{PI, TAU};
 
 pub type ID = (u64, u64);
 
 // Collection
 
-#[derive(NifStruct)]
-#[module = "Yarnballs.Missiles"]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.Missiles")]
 pub struct Missiles {
     pub entities: Vec<Missile>,
     // TODO: ideally, this can be HashSet at some point: https://github.com/rusterlium/rustler/pull/408
@@ -32,24 +37,70 @@ impl Missiles {
         }
     }
 
-    pub fn update(&mut self) {
+    // Homing missiles turn towards the enemy nearest to them.
+    pub fn update(&mut self, enemies: &Enemies, now: i64) {
         let remove_ids = &self.remove_ids;
         self.entities
             .retain(|e| e.lifespan > 0 && !remove_ids.contains_key(&e.id));
-        self.entities.iter_mut().for_each(|e| e.update());
+        self.entities.iter_mut().for_each(|e| {
+            let target = if e.stats().homing > 0. {
+                nearest(enemies, e.center())
+            } else {
+                None
+            };
+            e.update(target, now)
+        });
         self.remove_ids.clear()
     }
 
-    pub fn apply_enemy_collisions(&mut self, emcs: &Vec<collision::EnemyMissileCollision>) {
-        let ids = emcs.iter().map(|(_, m)| *m).collect();
+    // The missile, when it is still flying and has not gone through the given ship already.
+    pub fn hitting(&self, id: &ID, ship_id: &ship::ID) -> Option<&Missile> {
+        if self.remove_ids.contains_key(id) {
+            return None;
+        }
+        self.entities
+            .iter()
+            .find(|m| m.id == *id && !m.pierced.contains(ship_id))
+    }
+
+    // A missile that hit a ship is removed, unless it pierces.
+    pub fn spend(&mut self, id: &ID, ship_id: &ship::ID) {
+        match self.entities.iter_mut().find(|m| m.id == *id) {
+            Some(m) if m.stats().pierces => m.pierced.push(ship_id.clone()),
+            Some(_) => self.remove(vec![*id]),
+            None => (),
+        }
+    }
+
+    pub fn apply_enemy_collisions(&mut self, emcs: &[collision::EnemyMissileCollision]) {
+        let ids = emcs
+            .iter()
+            .map(|(_, m)| *m)
+            .filter(|id| {
+                !self
+                    .entities
+                    .iter()
+                    .any(|m| m.id == *id && m.stats().pierces)
+            })
+            .collect();
         self.remove(ids);
     }
 
-    pub fn shooter_counts(&self, ids: &Vec<ID>) -> HashMap<ship::ID, i64> {
-        let set: HashSet<&ID> = ids.into_iter().collect();
+    pub fn frame(&self, section: &mut Section<ID>) {
         self.entities
             .iter()
-            .filter(|m| set.contains(&m.id))
+            .for_each(|m| section.insert(m.id, |w| m.write(w)));
+    }
+
+    // A point for every missile that hit, or for every enemy a piercing missile went through.
+    pub fn shooter_counts(
+        &self,
+        emcs: &[collision::EnemyMissileCollision],
+    ) -> HashMap<ship::ID, i64> {
+        let mut counted: HashSet<&ID> = HashSet::new();
+        emcs.iter()
+            .filter_map(|(_, id)| self.entities.iter().find(|m| m.id == *id))
+            .filter(|m| m.stats().pierces || counted.insert(&m.id))
             .fold(HashMap::new(), {
                 |mut map, m| {
                     // TODO: avoid clone?
@@ -62,59 +113,129 @@ impl Missiles {
     }
 }
 
+// The center of the enemy nearest to a point, ties going to the lowest ID so that homing does
+// not depend on the order enemies are stored in.
+fn nearest(enemies: &Enemies, (x, y): (f64, f64)) -> Option<(f64, f64)> {
+    enemies
+        .entities()
+        .map(|e| {
+            let (ex, ey) = e.center();
+            ((ex - x).powi(2) + (ey - y).powi(2), e.id(), (ex, ey))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)))
+        .map(|(_, _, center)| center)
+}
+
 // Entity
 
-#[derive(NifStruct, Clone)]
-#[module = "Yarnballs.Missile"]
+#[derive(Clone)]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.Missile")]
 pub struct Missile {
     pub id: ID,
     shooter_id: String,
     updated_at: i64,
     x: f64,
     y: f64,
+    // where the missile was at the start of the last tick
+    prev_x: f64,
+    prev_y: f64,
     vel_x: f64,
     vel_y: f64,
     lifespan: i64,
+    weapon: Weapon,
+    // ships a piercing missile already went through
+    pierced: Vec<ship::ID>,
 }
 
-const LIFESPAN: i64 = 1000;
-const VEL: f64 = 500.;
-
 impl Missile {
     // TODO: consider passing a map?
-    pub fn spawn(shooter_id: String, x: f64, y: f64, angle: f64) -> Self {
-        let vel_x = VEL * angle.cos();
-        let vel_y = VEL * angle.sin();
+    pub fn spawn(
+        shooter_id: String,
+        x: f64,
+        y: f64,
+        angle: f64,
+        weapon: Weapon,
+        rng: &mut Pcg32,
+        now: i64,
+    ) -> Self {
+        let speed = weapon.stats().speed;
+        let vel_x = speed * angle.cos();
+        let vel_y = speed * angle.sin();
 
         Self {
-            id: utils::new_uuid_as_u64_pair(),
+            id: utils::new_uuid_as_u64_pair(rng),
             shooter_id,
-            updated_at: utils::now_in_millis(),
+            updated_at: now,
             x,
             y,
+            prev_x: x,
+            prev_y: y,
             vel_x,
             vel_y,
-            lifespan: LIFESPAN,
+            lifespan: weapon.stats().lifespan,
+            weapon,
+            pierced: Vec::new(),
         }
     }
 
-    fn update(&mut self) {
-        let updated_at = utils::now_in_millis();
-        let dt = updated_at - self.updated_at;
+    pub fn shooter_id(&self) -> &ship::ID {
+        &self.shooter_id
+    }
+
+    // dealt to the ships of other players, when they can be hit
+    pub fn damage(&self) -> f64 {
+        self.stats().damage
+    }
+
+    fn stats(&self) -> &'static Stats {
+        self.weapon.stats()
+    }
+
+    fn write(&self, w: &mut Writer) {
+        w.id(self.id);
+        w.position(self.x, self.y);
+        w.velocity(self.vel_x, self.vel_y);
+        w.u8(self.weapon.kind());
+    }
+
+    fn update(&mut self, target: Option<(f64, f64)>, now: i64) {
+        let dt = now - self.updated_at;
 
-        self.x = self.x + self.vel_x * ((dt as f64) / 1000.0);
-        self.y = self.y + self.vel_y * ((dt as f64) / 1000.0);
-        self.lifespan = self.lifespan - dt;
-        self.updated_at = updated_at;
+        if let Some((x, y)) = target {
+            self.turn_towards(x, y, dt);
+        }
+        (self.prev_x, self.prev_y) = (self.x, self.y);
+        self.x += self.vel_x * ((dt as f64) / 1000.0);
+        self.y += self.vel_y * ((dt as f64) / 1000.0);
+        self.lifespan -= dt;
+        self.updated_at = now;
+    }
+
+    // Turn the shortest way round, no faster than the weapon allows, keeping the same speed.
+    fn turn_towards(&mut self, x: f64, y: f64, dt: i64) {
+        let (cx, cy) = self.center();
+        let heading = self.vel_y.atan2(self.vel_x);
+        let off = ((y - cy).atan2(x - cx) - heading + PI).rem_euclid(TAU) - PI;
+        let max_turn = self.stats().homing * (dt as f64) / 1000.;
+        let heading = heading + off.clamp(-max_turn, max_turn);
+
+        let speed = self.stats().speed;
+        self.vel_x = speed * heading.cos();
+        self.vel_y = speed * heading.sin();
     }
 }
 
 impl collision::Circle for Missile {
     fn radius(&self) -> f64 {
-        5.
+        self.stats().radius
     }
 
     fn center(&self) -> (f64, f64) {
         (self.x + self.radius(), self.y + self.radius())
     }
+
+    fn prev_center(&self) -> (f64, f64) {
+        (self.prev_x + self.radius(), self.prev_y + self.radius())
+    }
 }
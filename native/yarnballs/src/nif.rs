@@ -0,0 +1,239 @@
+// The NIFs behind `Yarnballs.Native`.
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::event::Event;
+use crate::registry;
+use crate::replay::{self, Recording};
+use crate::state::State;
+use crate::world::{Handle, World};
+use rustler::{Binary, Decoder, Encoder, Env, OwnedBinary, Term};
+
+// Every NIF returns `{:ok, value}` or `{:error, reason}`, see `error::Error` for the reasons.
+// Arguments are decoded by hand so that a bad argument is reported as an error too, rather than
+// raised.
+
+fn decode<'a, T: Decoder<'a>>(term: Term<'a>, argument: &str) -> Result<T, Error> {
+    term.decode()
+        .map_err(|_| Error::Decode(argument.to_string()))
+}
+
+// State
+//
+// Commands mutate the world in place and hand back the same handle, so the Elixir side can keep
+// piping it through. Only `render`, `snapshot` and `delta` materialise the world as terms.
+
+#[rustler::nif]
+fn init_state(config: Term) -> Result<Handle, Error> {
+    let config: Config = decode(config, "config")?;
+    State::init(config).map(World::init)
+}
+
+#[rustler::nif]
+fn init_recorded_state(config: Term) -> Result<Handle, Error> {
+    let config: Config = decode(config, "config")?;
+    State::init_recorded(config).map(World::init)
+}
+
+#[rustler::nif]
+fn spawn_ship<'a>(
+    world: Term<'a>,
+    id: Term<'a>,
+    name: Term<'a>,
+    team: Term<'a>,
+) -> Result<Handle, Error> {
+    let world: Handle = decode(world, "state")?;
+    world.state()?.spawn_ship(
+        decode(id, "id")?,
+        decode(name, "name")?,
+        decode(team, "team")?,
+    );
+    Ok(world)
+}
+
+#[rustler::nif]
+fn turn_ship<'a>(world: Term<'a>, id: Term<'a>, clockwise: Term<'a>) -> Result<Handle, Error> {
+    let world: Handle = decode(world, "state")?;
+    world
+        .state()?
+        .turn_ship(decode(id, "id")?, decode(clockwise, "clockwise")?)?;
+    Ok(world)
+}
+
+#[rustler::nif]
+fn thrust_ship<'a>(world: Term<'a>, id: Term<'a>) -> Result<Handle, Error> {
+    let world: Handle = decode(world, "state")?;
+    world.state()?.thrust_ship(decode(id, "id")?)?;
+    Ok(world)
+}
+
+#[rustler::nif]
+fn fire_missile_or_respawn<'a>(world: Term<'a>, id: Term<'a>) -> Result<Handle, Error> {
+    let world: Handle = decode(world, "state")?;
+    world.state()?.fire_missile_or_respawn(decode(id, "id")?)?;
+    Ok(world)
+}
+
+#[rustler::nif]
+fn update_bodies(world: Term) -> Result<Handle, Error> {
+    let world: Handle = decode(world, "state")?;
+    world.state()?.update();
+    Ok(world)
+}
+
+#[rustler::nif(name = "update_bodies")]
+fn update_bodies_by<'a>(world: Term<'a>, dt: Term<'a>) -> Result<Handle, Error> {
+    let world: Handle = decode(world, "state")?;
+    world.state()?.update_by(decode(dt, "dt")?);
+    Ok(world)
+}
+
+#[rustler::nif]
+fn remove_ship<'a>(world: Term<'a>, id: Term<'a>) -> Result<Handle, Error> {
+    let world: Handle = decode(world, "state")?;
+    world.state()?.remove_ship(decode(id, "id")?)?;
+    Ok(world)
+}
+
+#[rustler::nif]
+fn switch_weapon<'a>(world: Term<'a>, id: Term<'a>, weapon: Term<'a>) -> Result<Handle, Error> {
+    let world: Handle = decode(world, "state")?;
+    world
+        .state()?
+        .switch_weapon(decode(id, "id")?, decode(weapon, "weapon")?)?;
+    Ok(world)
+}
+
+#[rustler::nif]
+fn total_score(world: Term) -> Result<i64, Error> {
+    let world: Handle = decode(world, "state")?;
+    let total_score = world.state()?.total_score();
+    Ok(total_score)
+}
+
+#[rustler::nif]
+fn level(world: Term) -> Result<u32, Error> {
+    let world: Handle = decode(world, "state")?;
+    let level = world.state()?.level();
+    Ok(level)
+}
+
+#[rustler::nif]
+fn next_level_score(world: Term) -> Result<(u32, Option<u32>), Error> {
+    let world: Handle = decode(world, "state")?;
+    let next_level_score = world.state()?.next_level_score();
+    Ok(next_level_score)
+}
+
+// The same for a single team.
+
+#[rustler::nif(name = "total_score")]
+fn team_score<'a>(world: Term<'a>, team: Term<'a>) -> Result<i64, Error> {
+    let world: Handle = decode(world, "state")?;
+    let team_score = world.state()?.team_score(&decode(team, "team")?);
+    Ok(team_score)
+}
+
+#[rustler::nif(name = "level")]
+fn team_level<'a>(world: Term<'a>, team: Term<'a>) -> Result<u32, Error> {
+    let world: Handle = decode(world, "state")?;
+    let level = world.state()?.team_level(&decode(team, "team")?);
+    Ok(level)
+}
+
+#[rustler::nif(name = "next_level_score")]
+fn team_next_level_score<'a>(world: Term<'a>, team: Term<'a>) -> Result<(u32, Option<u32>), Error> {
+    let world: Handle = decode(world, "state")?;
+    let next_level_score = world.state()?.team_next_level_score(&decode(team, "team")?);
+    Ok(next_level_score)
+}
+
+#[rustler::nif]
+fn drain_events(world: Term) -> Result<Vec<Event>, Error> {
+    let world: Handle = decode(world, "state")?;
+    let events = world.state()?.drain_events();
+    Ok(events)
+}
+
+// Rendering
+
+#[rustler::nif]
+fn render<'a>(env: Env<'a>, world: Term<'a>) -> Result<Term<'a>, Error> {
+    let world: Handle = decode(world, "state")?;
+    let term = world.state()?.encode(env);
+    Ok(term)
+}
+
+#[rustler::nif]
+fn snapshot<'a>(env: Env<'a>, world: Term<'a>) -> Result<Binary<'a>, Error> {
+    let world: Handle = decode(world, "state")?;
+    let bytes = world.state()?.snapshot();
+    to_binary(env, bytes)
+}
+
+#[rustler::nif]
+fn delta<'a>(env: Env<'a>, world: Term<'a>) -> Result<Binary<'a>, Error> {
+    let world: Handle = decode(world, "state")?;
+    to_binary(env, world.delta()?)
+}
+
+fn to_binary(env: Env, bytes: Vec<u8>) -> Result<Binary, Error> {
+    let mut binary = OwnedBinary::new(bytes.len()).ok_or(Error::OutOfMemory)?;
+    binary.as_mut_slice().copy_from_slice(&bytes);
+    Ok(binary.release(env))
+}
+
+// Rooms
+//
+// Worlds registered by room id. The handles they return work with every state NIF above.
+
+#[rustler::nif]
+fn create_world<'a>(room_id: Term<'a>, config: Term<'a>) -> Result<Handle, Error> {
+    registry::create(decode(room_id, "room_id")?, decode(config, "config")?)
+}
+
+#[rustler::nif]
+fn fetch_world(room_id: Term) -> Result<Handle, Error> {
+    let room_id: String = decode(room_id, "room_id")?;
+    registry::fetch(&room_id)
+}
+
+#[rustler::nif]
+fn world_config(room_id: Term) -> Result<Config, Error> {
+    let room_id: String = decode(room_id, "room_id")?;
+    registry::config(&room_id)
+}
+
+#[rustler::nif]
+fn list_worlds() -> Result<Vec<String>, Error> {
+    registry::list()
+}
+
+#[rustler::nif]
+fn destroy_world(room_id: Term) -> Result<String, Error> {
+    let room_id: String = decode(room_id, "room_id")?;
+    registry::destroy(&room_id).map(|_| room_id)
+}
+
+// Replay
+
+#[rustler::nif]
+fn recording(world: Term) -> Result<Option<Recording>, Error> {
+    let world: Handle = decode(world, "state")?;
+    let recording = world.state()?.recording().cloned();
+    Ok(recording)
+}
+
+#[rustler::nif]
+fn replay(recording: Term) -> Result<Handle, Error> {
+    let recording: Recording = decode(recording, "recording")?;
+    replay::replay(&recording, None).map(World::init)
+}
+
+#[rustler::nif(name = "replay")]
+fn replay_until<'a>(recording: Term<'a>, until: Term<'a>) -> Result<Handle, Error> {
+    let recording: Recording = decode(recording, "recording")?;
+    replay::replay(&recording, Some(decode(until, "until")?)).map(World::init)
+}
+
+rustler::init!("Elixir.Yarnballs.Native");
@@ -0,0 +1,143 @@
+use crate::rng::Pcg32;
+use crate::snapshot::{Section, Writer};
+use crate::{collision, effect, utils};
+use rand::{seq::SliceRandom, Rng};
+use std::collections::HashMap;
+
+pub type ID = (u64, u64);
+
+// What a ship gets out of picking a power-up up. All but health packs last a while.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "nif", derive(rustler::NifUnitEnum))]
+pub enum Kind {
+    Shield,
+    HealthPack,
+    TripleShot,
+    SpeedBoost,
+    ScoreMultiplier,
+}
+
+const KINDS: [Kind; 5] = [
+    Kind::Shield,
+    Kind::HealthPack,
+    Kind::TripleShot,
+    Kind::SpeedBoost,
+    Kind::ScoreMultiplier,
+];
+
+impl Kind {
+    // The effect it has on the ship that picks it up, if it lasts.
+    pub fn effect(self) -> Option<effect::Kind> {
+        match self {
+            Self::Shield => Some(effect::Kind::Shield),
+            Self::HealthPack => None,
+            Self::TripleShot => Some(effect::Kind::TripleShot),
+            Self::SpeedBoost => Some(effect::Kind::SpeedBoost),
+            Self::ScoreMultiplier => Some(effect::Kind::ScoreMultiplier),
+        }
+    }
+}
+
+// Collection
+
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.PowerUps")]
+pub struct PowerUps {
+    pub entities: Vec<PowerUp>,
+    // TODO: ideally, this can be HashSet at some point: https://github.com/rusterlium/rustler/pull/408
+    remove_ids: HashMap<ID, bool>,
+}
+
+impl PowerUps {
+    pub fn init() -> Self {
+        Self {
+            entities: Vec::new(),
+            remove_ids: HashMap::new(),
+        }
+    }
+
+    // Maybe leave a power-up of a random kind where an enemy was destroyed.
+    pub fn drop_at(&mut self, (x, y): (f64, f64), chance: f64, rng: &mut Pcg32, now: i64) {
+        if rng.gen::<f64>() < chance {
+            let kind = *KINDS.choose(rng).unwrap();
+            self.entities
+                .push(PowerUp::spawn(kind, x - RADIUS, y - RADIUS, rng, now));
+        }
+    }
+
+    pub fn update(&mut self, now: i64) {
+        let remove_ids = &self.remove_ids;
+        self.entities
+            .retain(|p| p.lifespan > 0 && !remove_ids.contains_key(&p.id));
+        self.entities.iter_mut().for_each(|p| p.update(now));
+        self.remove_ids.clear()
+    }
+
+    // The kind of a power-up a ship ran into, unless another ship picked it up first.
+    pub fn pick_up(&mut self, id: &ID) -> Option<Kind> {
+        if self.remove_ids.contains_key(id) {
+            return None;
+        }
+        let kind = self.entities.iter().find(|p| p.id == *id)?.kind;
+        self.remove_ids.insert(*id, true);
+        Some(kind)
+    }
+
+    pub fn frame(&self, section: &mut Section<ID>) {
+        self.entities
+            .iter()
+            .for_each(|p| section.insert(p.id, |w| p.write(w)));
+    }
+}
+
+// Entity
+
+#[derive(Clone)]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.PowerUp")]
+pub struct PowerUp {
+    pub id: ID,
+    kind: Kind,
+    updated_at: i64,
+    x: f64,
+    y: f64,
+    lifespan: i64,
+}
+
+// left lying around for this long
+const LIFESPAN: i64 = 10_000;
+const RADIUS: f64 = 15.;
+
+impl PowerUp {
+    pub fn spawn(kind: Kind, x: f64, y: f64, rng: &mut Pcg32, now: i64) -> Self {
+        Self {
+            id: utils::new_uuid_as_u64_pair(rng),
+            kind,
+            updated_at: now,
+            x,
+            y,
+            lifespan: LIFESPAN,
+        }
+    }
+
+    fn write(&self, w: &mut Writer) {
+        w.u8(self.kind as u8);
+        w.id(self.id);
+        w.position(self.x, self.y);
+    }
+
+    fn update(&mut self, now: i64) {
+        self.lifespan -= now - self.updated_at;
+        self.updated_at = now;
+    }
+}
+
+impl collision::Circle for PowerUp {
+    fn radius(&self) -> f64 {
+        RADIUS
+    }
+
+    fn center(&self) -> (f64, f64) {
+        (self.x + RADIUS, self.y + RADIUS)
+    }
+}
@@ -0,0 +1,63 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::state::State;
+use crate::world::{Handle, World};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+
+// Worlds by room, so that one node can host many matches at once. Each room keeps the config it
+// was created with, seed included, so it can be inspected or started over.
+struct Room {
+    config: Config,
+    world: Handle,
+}
+
+static ROOMS: Mutex<BTreeMap<String, Room>> = Mutex::new(BTreeMap::new());
+
+fn rooms() -> Result<MutexGuard<'static, BTreeMap<String, Room>>, Error> {
+    ROOMS.lock().map_err(|_| Error::Poisoned)
+}
+
+pub fn create(id: String, config: Config) -> Result<Handle, Error> {
+    let mut rooms = rooms()?;
+    if rooms.contains_key(&id) {
+        return Err(Error::RoomTaken(id));
+    }
+
+    let config = config.seeded();
+    let world = World::init(State::init(config.clone())?);
+    rooms.insert(
+        id,
+        Room {
+            config,
+            world: world.clone(),
+        },
+    );
+    Ok(world)
+}
+
+pub fn fetch(id: &str) -> Result<Handle, Error> {
+    rooms()?
+        .get(id)
+        .map(|room| room.world.clone())
+        .ok_or_else(|| Error::UnknownRoom(id.to_string()))
+}
+
+pub fn config(id: &str) -> Result<Config, Error> {
+    rooms()?
+        .get(id)
+        .map(|room| room.config.clone())
+        .ok_or_else(|| Error::UnknownRoom(id.to_string()))
+}
+
+pub fn list() -> Result<Vec<String>, Error> {
+    Ok(rooms()?.keys().cloned().collect())
+}
+
+// Handles already given out keep the world alive until they are garbage collected.
+pub fn destroy(id: &str) -> Result<(), Error> {
+    rooms()?
+        .remove(id)
+        .map(|_| ())
+        .ok_or_else(|| Error::UnknownRoom(id.to_string()))
+}
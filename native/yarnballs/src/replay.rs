@@ -0,0 +1,159 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::state::State;
+use crate::weapon::Weapon;
+
+// Commands accepted by a `State`, mirroring the NIFs that mutate it.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "nif", derive(rustler::NifTaggedEnum))]
+pub enum Command {
+    SpawnShip {
+        id: String,
+        name: Option<String>,
+        team: Option<String>,
+    },
+    TurnShip {
+        id: String,
+        clockwise: bool,
+    },
+    ThrustShip {
+        id: String,
+    },
+    FireMissileOrRespawn {
+        id: String,
+    },
+    RemoveShip {
+        id: String,
+    },
+    SwitchWeapon {
+        id: String,
+        weapon: Weapon,
+    },
+    UpdateBodies {
+        dt: i64,
+    },
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.Recording.Entry")]
+pub struct Entry {
+    // simulation time once the command was applied
+    at: i64,
+    command: Command,
+}
+
+// Every command applied to a state since it was seeded, in order.
+#[derive(Clone)]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.Recording")]
+pub struct Recording {
+    config: Config,
+    entries: Vec<Entry>,
+}
+
+impl Recording {
+    pub fn init(config: Config) -> Self {
+        Self {
+            config,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, at: i64, command: Command) {
+        self.entries.push(Entry { at, command });
+    }
+}
+
+/*
+Rebuild a state by applying a recording to a freshly seeded state.
+
+When `until` is given, only the commands applied up to and including that simulation time are
+replayed, which allows stepping through a match frame by frame.
+*/
+pub fn replay(recording: &Recording, until: Option<i64>) -> Result<State, Error> {
+    let mut state = State::init_recorded(recording.config.clone())?;
+    recording
+        .entries
+        .iter()
+        .take_while(|e| until.is_none_or(|until| e.at <= until))
+        .try_for_each(|e| state.apply(e.command.clone()))?;
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Pcg32;
+    use crate::weapon::Weapon;
+    use rand::Rng;
+
+    fn config() -> Config {
+        Config {
+            seed: Some(7),
+            ship_bounce: Some(0.5),
+            pvp: Some(true),
+            power_up_chance: Some(0.5),
+            ..Config::default()
+        }
+    }
+
+    // A session of random commands, drawn from their own generator so the world's is untouched.
+    // Commands for ships that are gone fail, and are left out of the recording.
+    fn session(state: &mut State, commands: usize) {
+        let mut rng = Pcg32::seed(1);
+        for _ in 0..commands {
+            let id = format!("ship-{}", rng.gen_range(0..3));
+            let command = match rng.gen_range(0..8) {
+                0 => Command::SpawnShip {
+                    id,
+                    name: None,
+                    team: None,
+                },
+                1 => Command::TurnShip {
+                    id,
+                    clockwise: rng.gen(),
+                },
+                2 => Command::ThrustShip { id },
+                3 => Command::FireMissileOrRespawn { id },
+                4 => Command::SwitchWeapon {
+                    id,
+                    weapon: Weapon::Laser,
+                },
+                5 if rng.gen_ratio(1, 10) => Command::RemoveShip { id },
+                _ => Command::UpdateBodies {
+                    dt: rng.gen_range(0..100),
+                },
+            };
+            state.apply(command).ok();
+        }
+    }
+
+    #[test]
+    fn replays_rebuild_the_recorded_world() {
+        let mut state = State::init_recorded(config()).unwrap();
+        session(&mut state, 2000);
+
+        let replayed = replay(state.recording().unwrap(), None).unwrap();
+        assert_eq!(replayed.snapshot(), state.snapshot());
+        assert_eq!(
+            replayed.recording().unwrap().entries.len(),
+            state.recording().unwrap().entries.len()
+        );
+    }
+
+    #[test]
+    fn replays_stop_at_the_given_time() {
+        let mut state = State::init_recorded(config()).unwrap();
+        session(&mut state, 1000);
+        let halfway = state.snapshot();
+        let until = state.recording().unwrap().entries.last().unwrap().at;
+        // the next update moves the clock on, leaving the commands above behind `until`
+        state.apply(Command::UpdateBodies { dt: 100 }).unwrap();
+        session(&mut state, 1000);
+
+        let replayed = replay(state.recording().unwrap(), Some(until)).unwrap();
+        assert_eq!(replayed.snapshot(), halfway);
+        assert_ne!(replayed.snapshot(), state.snapshot());
+    }
+}
@@ -1,14 +1,24 @@
-use crate::{collision, utils};
+use crate::arena::Arena;
+use crate::effect::{self, Effects};
+use crate::error::Error;
+use crate::rng::Pcg32;
+use crate::snapshot::{Section, Writer};
+use crate::weapon::Weapon;
+use crate::{clock, collision, power_up, utils};
 use crate::{enemy::Enemy, missile::Missile};
-use rustler::NifStruct;
 use std::collections::HashMap;
+use std::f64::consts::PI;
 
 pub type ID = String;
 
+// Ships in the same team share a score and level progression, and may be kept from shooting
+// each other. Ships without a team play for themselves.
+pub type Team = String;
+
 // Collection
 
-#[derive(NifStruct)]
-#[module = "Yarnballs.PlayerShips"]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.PlayerShips")]
 pub struct Ships {
     entities: HashMap<ID, Ship>,
 }
@@ -20,70 +30,170 @@ impl Ships {
         }
     }
 
-    pub fn entities(&self) -> Vec<&Ship> {
-        self.entities.values().collect()
+    pub fn entities(&self) -> impl Iterator<Item = &Ship> {
+        self.entities.values()
     }
 
     pub fn total_score(&self) -> i64 {
         self.entities.values().map(|s| s.score).sum()
     }
 
+    pub fn team_score(&self, team: &Team) -> i64 {
+        self.entities
+            .values()
+            .filter(|s| s.team.as_ref() == Some(team))
+            .map(|s| s.score)
+            .sum()
+    }
+
+    pub fn are_teammates(&self, id1: &ID, id2: &ID) -> bool {
+        let team = |id| self.entities.get(id).and_then(|s: &Ship| s.team.as_ref());
+        team(id1).is_some() && team(id1) == team(id2)
+    }
+
     pub fn increase_score(&mut self, id: ID, points: i64) {
-        self.entities.entry(id).and_modify(|e| e.score += points);
+        self.entities
+            .entry(id)
+            .and_modify(|e| e.score += points * e.score_multiplier());
     }
 
-    pub fn is_dead(&self, id: &ID) -> Option<bool> {
-        self.entities.get(id).map(|e| e.is_dead())
+    fn get(&self, id: &ID) -> Result<&Ship, Error> {
+        self.entities
+            .get(id)
+            .ok_or_else(|| Error::UnknownShip(id.clone()))
     }
 
-    pub fn spawn_missile(&self, id: &ID) -> Option<Missile> {
-        self.entities.get(id).map(|e| e.spawn_missile())
+    fn get_mut(&mut self, id: &ID) -> Result<&mut Ship, Error> {
+        self.entities
+            .get_mut(id)
+            .ok_or_else(|| Error::UnknownShip(id.clone()))
     }
 
-    pub fn spawn(&mut self, id: ID, name: Option<String>) {
-        let ship = spawn(id.clone(), name);
-        self.entities.insert(id.clone(), ship);
+    pub fn is_dead(&self, id: &ID) -> Result<bool, Error> {
+        self.get(id).map(|e| e.is_dead())
     }
 
-    pub fn update(&mut self) {
-        self.entities.retain(|_, s| !s.remove);
-        self.entities.values_mut().for_each(|s| s.update())
+    // Nothing is fired while the weapon is still cooling down from the previous shot.
+    pub fn fire(&mut self, id: &ID, rng: &mut Pcg32, now: i64) -> Result<Vec<Missile>, Error> {
+        self.get_mut(id).map(|e| e.fire(rng, now))
     }
 
-    pub fn respawn(&mut self, id: ID) {
-        self.entities.entry(id).and_modify(|s| s.respawn());
+    // The missiles a shot would fire, whether or not the weapon is ready.
+    pub fn spawn_missiles(
+        &self,
+        id: &ID,
+        rng: &mut Pcg32,
+        now: i64,
+    ) -> Result<Vec<Missile>, Error> {
+        self.get(id).map(|e| e.spawn_missiles(rng, now))
     }
 
-    pub fn turn(&mut self, id: ID, clockwise: bool) {
-        self.entities.entry(id).and_modify(|s| s.turn(clockwise));
+    pub fn switch_weapon(&mut self, id: &ID, weapon: Weapon) -> Result<(), Error> {
+        self.get_mut(id).map(|s| s.weapon = weapon)
     }
 
-    pub fn thrust(&mut self, id: ID) {
-        self.entities.entry(id).and_modify(|s| s.thrust());
+    pub fn spawn(&mut self, id: ID, name: Option<String>, team: Option<Team>, now: i64) {
+        let ship = spawn(id.clone(), name, team, now);
+        self.entities.insert(id, ship);
     }
 
-    pub fn collide_with(&mut self, id: ID, enemy: &Enemy) {
+    pub fn update(&mut self, arena: &Arena, now: i64) {
+        self.entities.retain(|_, s| !s.remove);
         self.entities
-            .entry(id)
-            .and_modify(|s| s.collide_with(enemy));
+            .values_mut()
+            .for_each(|s| s.update(arena, now))
+    }
+
+    pub fn frame(&self, section: &mut Section<ID>) {
+        self.entities
+            .values()
+            .for_each(|s| section.insert(s.id.clone(), |w| s.write(w)));
+    }
+
+    pub fn place(&mut self, id: &ID, x: f64, y: f64, angle: f64) -> Result<(), Error> {
+        self.get_mut(id).map(|s| {
+            (s.x, s.y, s.prev_x, s.prev_y) = (x, y, x, y);
+            s.angle = angle;
+        })
+    }
+
+    pub fn respawn(&mut self, id: &ID, now: i64) -> Result<(), Error> {
+        self.get_mut(id).map(|s| s.respawn(now))
+    }
+
+    pub fn turn(&mut self, id: &ID, clockwise: bool) -> Result<(), Error> {
+        self.get_mut(id).map(|s| s.turn(clockwise))
+    }
+
+    pub fn thrust(&mut self, id: &ID, now: i64) -> Result<(), Error> {
+        self.get_mut(id).map(|s| s.thrust(now))
+    }
+
+    pub fn collide_with(&mut self, id: &ID, enemy: &Enemy, arena: &Arena, now: i64) {
+        if let Some(s) = self.entities.get_mut(id) {
+            s.collide_with(enemy, arena, now);
+        }
+    }
+
+    pub fn can_power_up(&self, id: &ID) -> bool {
+        self.entities.get(id).is_some_and(|s| s.can_power_up())
     }
 
-    pub fn remove(&mut self, id: &ID) {
-        self.entities.remove(id);
+    pub fn power_up(&mut self, id: &ID, kind: power_up::Kind, now: i64) {
+        if let Some(s) = self.entities.get_mut(id) {
+            s.power_up(kind, now);
+        }
+    }
+
+    // Whether the hit took the ship down. Ships that are down already are not hit again.
+    pub fn hit(&mut self, id: &ID, damage: f64) -> bool {
+        self.entities.get_mut(id).is_some_and(|s| s.hit(damage))
+    }
+
+    // Bounce two ships that ran into each other apart, see `Ship::bump`.
+    pub fn bump(&mut self, id1: &ID, id2: &ID, arena: &Arena, bounce: f64) {
+        let (Some(ship1), Some(ship2)) = (self.entities.get(id1), self.entities.get(id2)) else {
+            return;
+        };
+        let (motion1, motion2) = (ship1.motion(), ship2.motion());
+        // the second ship may have been hit across an edge
+        let (x2, y2) = arena.nearest((motion1.x, motion1.y), (motion2.x, motion2.y));
+        // worked out once, as ships right on top of each other have no direction between them
+        let angle = utils::repel_angel(motion1.x, motion1.y, x2, y2);
+
+        if let Some(s) = self.entities.get_mut(id1) {
+            s.bump(motion2, angle, arena, bounce);
+        }
+        if let Some(s) = self.entities.get_mut(id2) {
+            s.bump(motion1, angle + PI, arena, bounce);
+        }
+    }
+
+    pub fn remove(&mut self, id: &ID) -> Result<(), Error> {
+        self.entities
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| Error::UnknownShip(id.clone()))
     }
 }
 
 // Entity
 
-#[derive(NifStruct, Clone)]
-#[module = "Yarnballs.PlayerShip"]
+#[derive(Clone)]
+#[cfg_attr(feature = "nif", derive(rustler::NifStruct))]
+#[cfg_attr(feature = "nif", module = "Yarnballs.PlayerShip")]
 pub struct Ship {
     pub id: ID,
     // TODO: must this be an option?
     name: Option<String>,
+    team: Option<Team>,
     updated_at: i64,
     x: f64,
     y: f64,
+    // where the ship was at the start of the last tick, on the same side of any edge it wrapped
+    // around as it is now
+    prev_x: f64,
+    prev_y: f64,
     vel_x: f64,
     vel_y: f64,
     angle: f64,
@@ -94,18 +204,51 @@ pub struct Ship {
     score: i64,
     destroyed_at: Option<i64>,
     remove: bool,
+    weapon: Weapon,
+    // when the weapon may fire again, going by the weapon that fired last
+    ready_at: i64,
+    // every shot heats the weapon up, and it cools down over time. One that reaches `MAX_HEAT`
+    // overheats, and does not fire again until it cooled down all the way.
+    heat: f64,
+    overheated: bool,
+    effects: Effects,
 }
 
-const MAX_HEALTH: f64 = 100.;
-const THRUST_DURATION: f64 = 50.;
+// Where a ship is and where it is headed, copied out so that it can be bumped into.
+#[derive(Clone, Copy)]
+struct Motion {
+    x: f64,
+    y: f64,
+    vel_x: f64,
+    vel_y: f64,
+}
 
-pub fn spawn(id: ID, name: Option<String>) -> Ship {
+pub const MAX_HEALTH: f64 = 100.;
+// awarded for shooting down another player, as much as going down costs
+pub const KILL_SCORE: i64 = 50;
+// taken off the score of a ship that goes down, though never below 0
+pub const DEATH_PENALTY: i64 = 50;
+const THRUST_DURATION: f64 = 50.;
+pub const MAX_HEAT: f64 = 100.;
+// per second
+const COOLING: f64 = 40.;
+const HEALTH_PACK: f64 = 50.;
+// how far the extra missiles of a triple shot fan out, in radians
+const TRIPLE_SHOT_SPREAD: f64 = 0.2;
+const SPEED_BOOST: f64 = 1.5;
+// taken off the acceleration for every stack
+const SLOW: f64 = 0.25;
+
+pub fn spawn(id: ID, name: Option<String>, team: Option<Team>, now: i64) -> Ship {
     Ship {
         id,
         name,
-        updated_at: utils::now_in_millis(),
+        team,
+        updated_at: now,
         x: 0.,
         y: 0.,
+        prev_x: 0.,
+        prev_y: 0.,
         vel_x: 0.,
         vel_y: 0.,
         angle: 0.,
@@ -116,6 +259,11 @@ pub fn spawn(id: ID, name: Option<String>) -> Ship {
         score: 0,
         destroyed_at: None,
         remove: false,
+        weapon: Weapon::default(),
+        ready_at: now,
+        heat: 0.,
+        overheated: false,
+        effects: Effects::default(),
     }
 }
 
@@ -128,55 +276,107 @@ impl Ship {
     const TURN_ACCELERATION: f64 = 300.;
     const TURN_FRICTION: f64 = 0.95;
 
-    fn spawn_missile(&self) -> Missile {
+    fn fire(&mut self, rng: &mut Pcg32, now: i64) -> Vec<Missile> {
+        if now < self.ready_at || self.overheated {
+            return Vec::new();
+        }
+        let stats = self.weapon.stats();
+        self.ready_at = now + stats.interval;
+        self.heat = (self.heat + stats.heat).min(MAX_HEAT);
+        self.overheated = self.heat >= MAX_HEAT;
+        self.spawn_missiles(rng, now)
+    }
+
+    // Shots of several missiles fan out evenly around where the ship is headed.
+    fn spawn_missiles(&self, rng: &mut Pcg32, now: i64) -> Vec<Missile> {
         let offset = self.radius() * self.angle;
         let x = offset.cos() + self.x + self.radius();
         let y = offset.sin() + self.y + self.radius();
-        Missile::spawn(self.id.clone(), x, y, self.angle)
+        let stats = self.weapon.stats();
+        let first = self.angle - stats.spread * (stats.shots - 1) as f64 / 2.;
+        let fan: &[f64] = if self.effects.has(effect::Kind::TripleShot) {
+            &[-TRIPLE_SHOT_SPREAD, 0., TRIPLE_SHOT_SPREAD]
+        } else {
+            &[0.]
+        };
+        (0..stats.shots)
+            .flat_map(|i| fan.iter().map(move |d| first + stats.spread * i as f64 + d))
+            .map(|angle| Missile::spawn(self.id.clone(), x, y, angle, self.weapon, rng, now))
+            .collect()
     }
 
     fn radius(&self) -> f64 {
         45.
     }
 
+    fn write(&self, w: &mut Writer) {
+        w.string(&self.id);
+        w.string(self.name.as_deref().unwrap_or_default());
+        w.position(self.x, self.y);
+        w.velocity(self.vel_x, self.vel_y);
+        w.angle(self.angle);
+        w.u8(self.health.round() as u8);
+        w.i64(self.score);
+        w.u8(self.thrusting as u8 | (self.is_dead() as u8) << 1 | (self.overheated as u8) << 2);
+        w.u8(self.weapon.kind());
+        w.u8(self.heat.round() as u8);
+        w.u8(self.effects.mask());
+    }
+
     fn turn(&mut self, clockwise: bool) {
         self.vel_angle += Self::TURN_ACCELERATION * if clockwise { 1. } else { -1. };
     }
 
-    fn thrust(&mut self) {
-        let vel_x = Self::ACCELERATION * self.angle.cos();
-        let vel_y = Self::ACCELERATION * self.angle.sin();
-
-        self.thrusted_at = utils::now_in_millis() as f64;
+    fn thrust(&mut self, now: i64) {
+        let boost = if self.effects.has(effect::Kind::SpeedBoost) {
+            SPEED_BOOST
+        } else {
+            1.
+        };
+        let slow = 1. - SLOW * self.effects.stacks(effect::Kind::Slow) as f64;
+        let acceleration = Self::ACCELERATION * boost * slow;
+        let vel_x = acceleration * self.angle.cos();
+        let vel_y = acceleration * self.angle.sin();
+
+        self.thrusted_at = now as f64;
         self.vel_x += vel_x;
         self.vel_y += vel_y;
     }
 
-    fn update(&mut self) {
-        self.update_position();
-        self.updated_health()
+    fn update(&mut self, arena: &Arena, now: i64) {
+        self.effects.update(now);
+        self.cool_down(now);
+        self.update_position(arena, now);
+        self.updated_health(now)
     }
 
-    fn update_position(&mut self) {
-        let updated_at = utils::now_in_millis();
-        let dt = (updated_at - self.updated_at) as f64;
-        let x = self.x + self.vel_x * (dt / 1000.);
-        let y = self.y + self.vel_y * (dt / 1000.);
+    fn cool_down(&mut self, now: i64) {
+        let dt = (now - self.updated_at) as f64;
+        self.heat = (self.heat - COOLING * (dt / 1000.)).max(0.);
+        self.overheated &= self.heat > 0.;
+    }
 
-        self.updated_at = updated_at;
-        self.x = utils::wrap_dim(x, utils::WIDTH as i64, self.radius());
-        self.y = utils::wrap_dim(y, utils::HEIGHT as i64, self.radius());
+    fn update_position(&mut self, arena: &Arena, now: i64) {
+        let dt = (now - self.updated_at) as f64;
+        let dx = self.vel_x * (dt / 1000.);
+        let dy = self.vel_y * (dt / 1000.);
 
-        self.angle = self.angle + self.vel_angle.to_radians() * (dt / 1000.);
+        self.updated_at = now;
+        self.x = utils::wrap_dim(self.x + dx, arena.width as i64, self.radius());
+        self.y = utils::wrap_dim(self.y + dy, arena.height as i64, self.radius());
+        (self.prev_x, self.prev_y) = (self.x - dx, self.y - dy);
 
-        self.vel_x = self.vel_x * (1. - Ship::THRUST_FRICTION);
-        self.vel_y = self.vel_y * (1. - Ship::THRUST_FRICTION);
-        self.vel_angle = self.vel_angle * (1. - Ship::TURN_FRICTION);
-        self.thrusting = (updated_at as f64) - self.thrusted_at < THRUST_DURATION;
+        self.angle += self.vel_angle.to_radians() * (dt / 1000.);
+
+        // friction is given per step
+        let steps = dt / clock::STEP as f64;
+        self.vel_x *= (1. - Ship::THRUST_FRICTION).powf(steps);
+        self.vel_y *= (1. - Ship::THRUST_FRICTION).powf(steps);
+        self.vel_angle *= (1. - Ship::TURN_FRICTION).powf(steps);
+        self.thrusting = (now as f64) - self.thrusted_at < THRUST_DURATION;
     }
 
-    fn updated_health(&mut self) {
-        let now = utils::now_in_millis();
+    fn updated_health(&mut self, now: i64) {
         if self.is_dead() {
             let dt = now - self.updated_at;
             // TODO: move this to a higher level
@@ -185,16 +385,19 @@ impl Ship {
             }
         } else if self.health <= 0. {
             self.destroyed_at = Some(now);
-            self.score = (self.score - 50).max(0);
+            self.effects.clear();
+            self.score = (self.score - DEATH_PENALTY).max(0);
         } else {
             self.health = (self.health + Ship::HEALTH_RECHARGE).min(MAX_HEALTH);
         }
     }
 
-    fn respawn(&mut self) {
+    // Ships come back where they went down, so they are left alone for a while.
+    fn respawn(&mut self, now: i64) {
         if self.is_dead() {
             self.destroyed_at = None;
             self.health = MAX_HEALTH;
+            self.effects.add(effect::Kind::Invulnerable, now);
         }
     }
 
@@ -202,13 +405,113 @@ impl Ship {
         self.destroyed_at.is_some()
     }
 
-    pub fn collide_with(&mut self, enemy: &Enemy) {
-        let new_angle = utils::repel_angel(self.x, self.y, enemy.x(), enemy.y());
+    pub fn health(&self) -> f64 {
+        self.health
+    }
+
+    pub fn heat(&self) -> f64 {
+        self.heat
+    }
+
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
+    pub fn angle(&self) -> f64 {
+        self.angle
+    }
+
+    pub fn collide_with(&mut self, enemy: &Enemy, arena: &Arena, now: i64) {
+        // the enemy may have been hit across an edge
+        let (enemy_x, enemy_y) = arena.nearest((self.x, self.y), (enemy.x(), enemy.y()));
+        let new_angle = utils::repel_angel(self.x, self.y, enemy_x, enemy_y);
         let repel_vel = enemy.repel_vel();
 
         self.vel_x = repel_vel * new_angle.cos();
         self.vel_y = repel_vel * new_angle.sin();
-        self.health -= enemy.damage();
+        if !self.effects.has(effect::Kind::Shield) && !self.effects.has(effect::Kind::Invulnerable)
+        {
+            self.health = (self.health - enemy.damage()).max(0.);
+            if let Some(effect) = enemy.effect() {
+                self.effects.add(effect, now);
+            }
+        }
+    }
+
+    // Ships that are down, or go down this tick, leave power-ups for others.
+    fn can_power_up(&self) -> bool {
+        !self.is_dead() && self.health > 0.
+    }
+
+    fn power_up(&mut self, kind: power_up::Kind, now: i64) {
+        if !self.can_power_up() {
+            return;
+        }
+        match kind.effect() {
+            Some(effect) => self.effects.add(effect, now),
+            None => self.health = (self.health + HEALTH_PACK).min(MAX_HEALTH),
+        }
+    }
+
+    fn score_multiplier(&self) -> i64 {
+        1 + self.effects.stacks(effect::Kind::ScoreMultiplier) as i64
+    }
+
+    pub fn effects(&self) -> &Effects {
+        &self.effects
+    }
+
+    fn hit(&mut self, damage: f64) -> bool {
+        if self.is_dead() || self.health <= 0. || self.effects.has(effect::Kind::Invulnerable) {
+            return false;
+        }
+        self.health = (self.health - damage).max(0.);
+        self.health <= 0.
+    }
+
+    fn motion(&self) -> Motion {
+        Motion {
+            x: self.x,
+            y: self.y,
+            vel_x: self.vel_x,
+            vel_y: self.vel_y,
+        }
+    }
+
+    /*
+    Bounce off another ship of the same mass, pushed away at `angle`, and move out of its way. The
+    other ship gets the mirror image of this, so both change velocity by the same amount in
+    opposite directions.
+
+    `bounce` is how much of the speed at which the ships close in on each other is left after the
+    bump: with 1 they trade it as in an elastic collision, with 0 they move on together.
+    */
+    fn bump(&mut self, other: Motion, angle: f64, arena: &Arena, bounce: f64) {
+        let (normal_x, normal_y) = (angle.cos(), angle.sin());
+
+        // ships that already move apart are left to it
+        let closing = (self.vel_x - other.vel_x) * normal_x + (self.vel_y - other.vel_y) * normal_y;
+        if closing < 0. {
+            let change = -(1. + bounce) / 2. * closing;
+            self.vel_x += change * normal_x;
+            self.vel_y += change * normal_y;
+        }
+
+        // each ship takes half of the way out, and the way it came this tick moves along so that
+        // the next collisions do not sweep across the push
+        let (other_x, other_y) = arena.nearest((self.x, self.y), (other.x, other.y));
+        let dist = ((self.x - other_x).powi(2) + (self.y - other_y).powi(2)).sqrt();
+        let overlap = 2. * self.radius() - dist;
+        if overlap > 0. {
+            let (came_x, came_y) = (self.x - self.prev_x, self.y - self.prev_y);
+            let (x, y) = (
+                self.x + overlap / 2. * normal_x,
+                self.y + overlap / 2. * normal_y,
+            );
+            self.x = utils::wrap_dim(x, arena.width as i64, self.radius());
+            self.y = utils::wrap_dim(y, arena.height as i64, self.radius());
+            (self.prev_x, self.prev_y) = (self.x - came_x, self.y - came_y);
+        }
     }
 }
 
@@ -220,4 +523,199 @@ impl collision::Circle for Ship {
     fn center(&self) -> (f64, f64) {
         (self.x + self.radius(), self.y + self.radius())
     }
+
+    fn prev_center(&self) -> (f64, f64) {
+        (self.prev_x + self.radius(), self.prev_y + self.radius())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enemy::{bouncer, rock};
+    use proptest::prelude::*;
+    use std::f64::consts::TAU;
+
+    // A ship flying right into one that sits still just ahead of it, after they bumped.
+    fn bumped(bounce: f64) -> (Ship, Ship) {
+        let arena = Arena::init(None, None).unwrap();
+        let (a, b) = ("a".to_string(), "b".to_string());
+        let mut ships = Ships::init();
+        ships.spawn(a.clone(), None, None, 0);
+        ships.spawn(b.clone(), None, None, 0);
+        ships.place(&a, 100., 100., 0.).unwrap();
+        ships.place(&b, 180., 100., 0.).unwrap();
+        ships.get_mut(&a).unwrap().vel_x = 100.;
+
+        ships.bump(&a, &b, &arena, bounce);
+        (
+            ships.get(&a).unwrap().clone(),
+            ships.get(&b).unwrap().clone(),
+        )
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn ships_pushed_over_an_edge_wrap_with_where_they_came_from() {
+        let arena = Arena::init(None, None).unwrap();
+        let (a, b) = ("a".to_string(), "b".to_string());
+        let mut ships = Ships::init();
+        ships.spawn(a.clone(), None, None, 0);
+        ships.spawn(b.clone(), None, None, 0);
+        ships.place(&a, 590., 100., 0.).unwrap();
+        ships.place(&b, 560., 100., 0.).unwrap();
+        ships.get_mut(&a).unwrap().prev_x = 585.;
+
+        ships.bump(&a, &b, &arena, 0.);
+        // 30 pixels apart, so each is pushed 30 pixels out, and "a" over the right edge
+        let (a, b) = (ships.get(&a).unwrap(), ships.get(&b).unwrap());
+        assert_eq!((a.x, a.prev_x), (-20., -25.));
+        assert_eq!((b.x, b.prev_x), (530., 530.));
+        assert_eq!((a.y, a.prev_y), (100., 100.));
+    }
+
+    #[test]
+    fn bouncers_slow_down_ships_without_a_shield() {
+        let arena = Arena::init(None, None).unwrap();
+        let mut rng = Pcg32::seed(1);
+        let bouncer = Enemy::Bouncer(bouncer::spawn(None, None, &arena, &mut rng, 0));
+        let rock = Enemy::Rock(rock::spawn(None, None, None, &arena, &mut rng, 0));
+        let (a, b) = ("a".to_string(), "b".to_string());
+        let mut ships = Ships::init();
+        ships.spawn(a.clone(), None, None, 0);
+        ships.spawn(b.clone(), None, None, 0);
+        ships.power_up(&b, power_up::Kind::Shield, 0);
+        for enemy in [&bouncer, &bouncer, &rock] {
+            ships.collide_with(&a, enemy, &arena, 0);
+            ships.collide_with(&b, enemy, &arena, 0);
+        }
+        let slow = |ships: &Ships, id| ships.get(id).unwrap().effects.stacks(effect::Kind::Slow);
+        assert_eq!((slow(&ships, &a), slow(&ships, &b)), (2, 0));
+
+        // from a standstill, a slowed ship picks up less speed
+        let speed = |ships: &mut Ships, id| {
+            let ship = ships.get_mut(id).unwrap();
+            (ship.vel_x, ship.vel_y) = (0., 0.);
+            ship.thrust(0);
+            ship.vel_x.hypot(ship.vel_y)
+        };
+        assert!(speed(&mut ships, &a) < speed(&mut ships, &b));
+    }
+
+    #[test]
+    fn weapons_fire_no_faster_than_they_allow() {
+        let mut rng = Pcg32::seed(1);
+        let mut ships = Ships::init();
+        let id = "a".to_string();
+        ships.spawn(id.clone(), None, None, 0);
+        let mut fire = |ships: &mut Ships, now| ships.fire(&id, &mut rng, now).unwrap().len();
+
+        assert_eq!(fire(&mut ships, 0), 1);
+        assert_eq!(fire(&mut ships, 100), 0);
+        assert_eq!(fire(&mut ships, 200), 1);
+
+        // the wait goes by the weapon that fired last
+        ships.switch_weapon(&id, Weapon::Spread).unwrap();
+        assert_eq!(fire(&mut ships, 400), 3);
+        assert_eq!(fire(&mut ships, 700), 0);
+        assert_eq!(fire(&mut ships, 800), 3);
+    }
+
+    #[test]
+    fn overheated_weapons_fire_again_once_cooled_down() {
+        let mut rng = Pcg32::seed(1);
+        let arena = Arena::init(None, None).unwrap();
+        let mut ships = Ships::init();
+        let id = "a".to_string();
+        ships.spawn(id.clone(), None, None, 0);
+        ships.switch_weapon(&id, Weapon::Laser).unwrap();
+        let mut fire = |ships: &mut Ships, now| ships.fire(&id, &mut rng, now).unwrap().len();
+
+        // four shots in a row, with no time to cool down in between
+        (0..4).for_each(|i| assert_eq!(fire(&mut ships, i * 600), 1));
+        let heat = |ships: &Ships| ships.get(&id).unwrap().heat();
+        assert_eq!(heat(&ships), MAX_HEAT);
+        assert_eq!(fire(&mut ships, 2400), 0);
+
+        // half cooled down is not enough
+        ships.update(&arena, 1250);
+        assert_eq!(heat(&ships), MAX_HEAT / 2.);
+        assert_eq!(fire(&mut ships, 2400), 0);
+        ships.update(&arena, 2500);
+        assert_eq!(heat(&ships), 0.);
+        assert_eq!(fire(&mut ships, 2500), 1);
+    }
+
+    #[test]
+    fn power_ups_wear_off() {
+        let mut rng = Pcg32::seed(1);
+        let arena = Arena::init(None, None).unwrap();
+        let mut ships = Ships::init();
+        let id = "a".to_string();
+        ships.spawn(id.clone(), None, None, 0);
+        let mut fire = |ships: &mut Ships, now| ships.fire(&id, &mut rng, now).unwrap().len();
+
+        ships.power_up(&id, power_up::Kind::TripleShot, 0);
+        assert_eq!(fire(&mut ships, 0), 3);
+        ships.update(&arena, effect::Kind::TripleShot.duration());
+        assert_eq!(fire(&mut ships, effect::Kind::TripleShot.duration()), 1);
+    }
+
+    #[test]
+    fn elastic_bumps_trade_velocities() {
+        let (a, b) = bumped(1.);
+        assert!(close(a.vel_x, 0.) && close(b.vel_x, 100.));
+        assert!(close(a.vel_y, 0.) && close(b.vel_y, 0.));
+        // and the ships no longer overlap
+        assert!(close(a.x, 95.) && close(b.x, 185.));
+    }
+
+    #[test]
+    fn inelastic_bumps_share_velocities() {
+        let (a, b) = bumped(0.);
+        assert!(close(a.vel_x, 50.) && close(b.vel_x, 50.));
+    }
+
+    #[test]
+    fn ships_on_top_of_each_other_are_pushed_apart() {
+        let arena = Arena::init(None, None).unwrap();
+        let (a, b) = ("a".to_string(), "b".to_string());
+        let mut ships = Ships::init();
+        ships.spawn(a.clone(), None, None, 0);
+        ships.spawn(b.clone(), None, None, 0);
+        ships.bump(&a, &b, &arena, 1.);
+
+        let (a, b) = (ships.get(&a).unwrap(), ships.get(&b).unwrap());
+        let dist = ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt();
+        assert!(close(dist, 2. * a.radius()));
+    }
+
+    proptest! {
+        #[test]
+        fn ships_wrap_around_the_arena(
+            width in 100..2000u32,
+            height in 100..2000u32,
+            position in (-1000. ..3000f64, -1000. ..3000f64),
+            angle in 0. ..TAU,
+            thrusts in 0..50usize,
+            dt in 0..5000i64,
+        ) {
+            let arena = Arena::init(Some(width.into()), Some(height.into())).unwrap();
+            let id = "a".to_string();
+            let mut ships = Ships::init();
+            ships.spawn(id.clone(), None, None, 0);
+            ships.place(&id, position.0, position.1, angle).unwrap();
+            for _ in 0..thrusts {
+                ships.thrust(&id, 0).unwrap();
+            }
+            ships.update(&arena, dt);
+
+            let (x, y) = collision::Circle::center(ships.get(&id).unwrap());
+            prop_assert!((0. ..arena.width).contains(&x));
+            prop_assert!((0. ..arena.height).contains(&y));
+        }
+    }
 }
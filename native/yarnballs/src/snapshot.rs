@@ -0,0 +1,493 @@
+/*
+Compact binary encodings of a `State`, meant to be pushed to clients as-is.
+
+All integers are little-endian. Every encoding starts with:
+
+    version: u8, kind: u8 (0 = full, 1 = delta), time: i64, level: u16, score: i64,
+    start_level_score: u32, next_level_score: u32 (u32::MAX when there is none),
+    width: u16, height: u16 (the arena size in whole pixels)
+
+A full snapshot then lists every ship, missile, enemy, explosion and power-up record, each
+section prefixed by a u32 count:
+
+    ship: id: string, name: string, x: i16, y: i16, vel_x: i16, vel_y: i16, angle: u16,
+          health: u8, score: i64, flags: u8 (1 = thrusting, 2 = dead, 4 = overheated),
+          weapon: u8, heat: u8, effects: u8 (1 = shield, 2 = triple shot, 4 = speed boost,
+          8 = score multiplier, 16 = invulnerable, 32 = slow)
+    missile: id: 16 bytes, x: i16, y: i16, vel_x: i16, vel_y: i16, weapon: u8
+    enemy: kind: u8 (0 = bouncer, 1 = rock), id: 16 bytes, x: i16, y: i16, vel_x: i16,
+           vel_y: i16, and for rocks only, scale: u8 (hundredths)
+    explosion: id: 16 bytes, x: i16, y: i16, size: u16, spawned_at: i64 (simulation time,
+               explosions fade out over a second)
+    power-up: kind: u8 (0 = shield, 1 = health pack, 2 = triple shot, 3 = speed boost,
+              4 = score multiplier), id: 16 bytes, x: i16, y: i16
+
+A delta against a previous frame instead has, for each of those five sections in the same
+order, three u32-counted lists: the records that spawned, the records that changed, and the IDs
+(string for ships, 16 bytes otherwise) that were removed.
+
+Weapons are 0 = single, 1 = spread, 2 = rapid, 3 = laser, 4 = homing.
+
+Strings are a u8 length followed by at most 255 bytes of UTF-8. Positions are quantised to
+quarter pixels, velocities to whole pixels per second and angles to 1/65536 of a turn, so an
+entity only counts as changed once its quantised record does. Values that do not fit saturate.
+*/
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::f64::consts::TAU;
+
+pub const VERSION: u8 = 6;
+
+pub const FULL: u8 = 0;
+pub const DELTA: u8 = 1;
+
+const POSITION_SCALE: f64 = 4.;
+
+pub struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub fn init() -> Self {
+        Self { bytes: Vec::new() }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn i16(&mut self, value: i16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn i64(&mut self, value: i64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Counts are never capped, they always match the records that follow.
+    pub fn count(&mut self, count: usize) {
+        self.u32(u32::try_from(count).expect("no more records than fit in memory"));
+    }
+
+    pub fn id(&mut self, id: (u64, u64)) {
+        let (hi, lo) = id;
+        self.bytes.extend_from_slice(&hi.to_be_bytes());
+        self.bytes.extend_from_slice(&lo.to_be_bytes());
+    }
+
+    pub fn string(&mut self, value: &str) {
+        let mut len = value.len().min(u8::MAX as usize);
+        while !value.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.u8(len as u8);
+        self.bytes.extend_from_slice(&value.as_bytes()[..len]);
+    }
+
+    pub fn position(&mut self, x: f64, y: f64) {
+        self.i16((x * POSITION_SCALE).round() as i16);
+        self.i16((y * POSITION_SCALE).round() as i16);
+    }
+
+    pub fn velocity(&mut self, vel_x: f64, vel_y: f64) {
+        self.i16(vel_x.round() as i16);
+        self.i16(vel_y.round() as i16);
+    }
+
+    pub fn angle(&mut self, angle: f64) {
+        let turns = angle.rem_euclid(TAU) / TAU;
+        self.u16((turns * 65536.).round() as u32 as u16);
+    }
+}
+
+pub trait Key: Ord {
+    fn write(&self, w: &mut Writer);
+}
+
+impl Key for String {
+    fn write(&self, w: &mut Writer) {
+        w.string(self);
+    }
+}
+
+impl Key for (u64, u64) {
+    fn write(&self, w: &mut Writer) {
+        w.id(*self);
+    }
+}
+
+// Encoded records keyed by entity ID.
+pub struct Section<K: Key> {
+    records: BTreeMap<K, Vec<u8>>,
+}
+
+impl<K: Key> Section<K> {
+    pub fn init() -> Self {
+        Self {
+            records: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, write: impl FnOnce(&mut Writer)) {
+        let mut w = Writer::init();
+        write(&mut w);
+        self.records.insert(key, w.into_bytes());
+    }
+
+    fn write_full(&self, w: &mut Writer) {
+        w.count(self.records.len());
+        self.records.values().for_each(|r| w.bytes(r));
+    }
+
+    fn write_delta(&self, prev: &Self, w: &mut Writer) {
+        let spawned: Vec<&Vec<u8>> = self
+            .records
+            .iter()
+            .filter(|(k, _)| !prev.records.contains_key(k))
+            .map(|(_, r)| r)
+            .collect();
+        let changed: Vec<&Vec<u8>> = self
+            .records
+            .iter()
+            .filter(|(k, r)| prev.records.get(k).is_some_and(|p| p != *r))
+            .map(|(_, r)| r)
+            .collect();
+        let removed: Vec<&K> = prev
+            .records
+            .keys()
+            .filter(|k| !self.records.contains_key(k))
+            .collect();
+
+        w.count(spawned.len());
+        spawned.into_iter().for_each(|r| w.bytes(r));
+        w.count(changed.len());
+        changed.into_iter().for_each(|r| w.bytes(r));
+        w.count(removed.len());
+        removed.into_iter().for_each(|k| k.write(w));
+    }
+}
+
+// Everything needed to encode a state, either in full or as a delta against an earlier frame.
+pub struct Frame {
+    pub header: Vec<u8>,
+    pub ships: Section<String>,
+    pub missiles: Section<(u64, u64)>,
+    pub enemies: Section<(u64, u64)>,
+    pub explosions: Section<(u64, u64)>,
+    pub power_ups: Section<(u64, u64)>,
+}
+
+impl Frame {
+    pub fn empty() -> Self {
+        Self {
+            header: Vec::new(),
+            ships: Section::init(),
+            missiles: Section::init(),
+            enemies: Section::init(),
+            explosions: Section::init(),
+            power_ups: Section::init(),
+        }
+    }
+
+    pub fn full(&self) -> Vec<u8> {
+        let mut w = self.start(FULL);
+        self.ships.write_full(&mut w);
+        self.missiles.write_full(&mut w);
+        self.enemies.write_full(&mut w);
+        self.explosions.write_full(&mut w);
+        self.power_ups.write_full(&mut w);
+        w.into_bytes()
+    }
+
+    pub fn delta(&self, prev: &Frame) -> Vec<u8> {
+        let mut w = self.start(DELTA);
+        self.ships.write_delta(&prev.ships, &mut w);
+        self.missiles.write_delta(&prev.missiles, &mut w);
+        self.enemies.write_delta(&prev.enemies, &mut w);
+        self.explosions.write_delta(&prev.explosions, &mut w);
+        self.power_ups.write_delta(&prev.power_ups, &mut w);
+        w.into_bytes()
+    }
+
+    fn start(&self, kind: u8) -> Writer {
+        let mut w = Writer::init();
+        w.u8(VERSION);
+        w.u8(kind);
+        w.bytes(&self.header);
+        w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::state::State;
+    use std::convert::TryInto;
+
+    const HEADER: usize = 8 + 2 + 8 + 4 + 4 + 2 + 2;
+    // the fixed part of a ship record, after its ID and name
+    const SHIP: usize = 4 * 2 + 2 + 1 + 8 + 4;
+    const SECTIONS: usize = 5;
+
+    fn config() -> Config {
+        Config {
+            seed: Some(1),
+            power_up_chance: Some(1.),
+            ..Config::default()
+        }
+    }
+
+    // A busy world: ships, missiles, enemies, and after a few ticks explosions and power-ups.
+    fn state() -> State {
+        let mut state = State::init(config()).unwrap();
+        state.populate(4, 100, 60, 0);
+        (0..10).for_each(|_| state.update());
+        state
+    }
+
+    struct Reader<'a> {
+        bytes: &'a [u8],
+    }
+
+    impl<'a> Reader<'a> {
+        fn take(&mut self, n: usize) -> &'a [u8] {
+            let (taken, rest) = self.bytes.split_at(n);
+            self.bytes = rest;
+            taken
+        }
+
+        fn u8(&mut self) -> u8 {
+            self.take(1)[0]
+        }
+
+        fn u16(&mut self) -> usize {
+            u16::from_le_bytes(self.take(2).try_into().unwrap()) as usize
+        }
+
+        fn count(&mut self) -> usize {
+            u32::from_le_bytes(self.take(4).try_into().unwrap()) as usize
+        }
+
+        // The key and the whole record, going by the layouts documented at the top.
+        fn record(&mut self, section: usize) -> (Vec<u8>, Vec<u8>) {
+            let start = self.bytes;
+            let key = self.key(section);
+            match section {
+                0 => {
+                    let name = self.u8() as usize;
+                    self.take(name + SHIP);
+                }
+                1 => {
+                    self.take(4 * 2 + 1);
+                }
+                2 => {
+                    let rock = start[0] == 1;
+                    self.take(4 * 2 + rock as usize);
+                }
+                3 => {
+                    self.take(2 * 2 + 2 + 8);
+                }
+                _ => {
+                    self.take(2 * 2);
+                }
+            }
+            let len = start.len() - self.bytes.len();
+            (key, start[..len].to_vec())
+        }
+
+        fn key(&mut self, section: usize) -> Vec<u8> {
+            match section {
+                0 => {
+                    let len = self.u8() as usize;
+                    self.take(len).to_vec()
+                }
+                // enemy and power-up records lead with their kind
+                2 | 4 => {
+                    self.u8();
+                    self.take(16).to_vec()
+                }
+                _ => self.take(16).to_vec(),
+            }
+        }
+
+        fn removed_key(&mut self, section: usize) -> Vec<u8> {
+            match section {
+                0 => {
+                    let len = self.u8() as usize;
+                    self.take(len).to_vec()
+                }
+                _ => self.take(16).to_vec(),
+            }
+        }
+    }
+
+    type Records = BTreeMap<Vec<u8>, Vec<u8>>;
+
+    struct Decoded {
+        header: Vec<u8>,
+        sections: Vec<Records>,
+    }
+
+    fn decode_full(bytes: &[u8]) -> Decoded {
+        let mut r = Reader { bytes };
+        assert_eq!((r.u8(), r.u8()), (VERSION, FULL));
+        let header = r.take(HEADER).to_vec();
+        let sections = (0..SECTIONS)
+            .map(|section| (0..r.count()).map(|_| r.record(section)).collect())
+            .collect();
+        assert!(r.bytes.is_empty());
+        Decoded { header, sections }
+    }
+
+    // Apply a delta on top of a decoded snapshot, counting spawned, changed and removed records.
+    fn apply_delta(decoded: &mut Decoded, bytes: &[u8]) -> [usize; 3] {
+        let mut r = Reader { bytes };
+        assert_eq!((r.u8(), r.u8()), (VERSION, DELTA));
+        decoded.header = r.take(HEADER).to_vec();
+        let mut counts = [0; 3];
+        for (section, records) in decoded.sections.iter_mut().enumerate() {
+            for count in &mut counts[..2] {
+                for _ in 0..r.count() {
+                    let (key, record) = r.record(section);
+                    records.insert(key, record);
+                    *count += 1;
+                }
+            }
+            for _ in 0..r.count() {
+                assert!(records.remove(&r.removed_key(section)).is_some());
+                counts[2] += 1;
+            }
+        }
+        assert!(r.bytes.is_empty());
+        counts
+    }
+
+    #[test]
+    fn snapshots_follow_the_documented_layout() {
+        let state = state();
+        let decoded = decode_full(&state.snapshot());
+
+        let mut header = Reader {
+            bytes: &decoded.header,
+        };
+        let time = i64::from_le_bytes(header.take(8).try_into().unwrap());
+        assert_eq!(time, 10 * crate::clock::STEP);
+        header.take(2 + 8 + 4 + 4);
+        assert_eq!((header.u16(), header.u16()), (640, 480));
+
+        // every section has records, so every layout was exercised
+        let counts: Vec<usize> = decoded.sections.iter().map(|s| s.len()).collect();
+        assert!(counts.iter().all(|&c| c > 0), "{:?}", counts);
+        assert_eq!(counts[0], 4);
+    }
+
+    #[test]
+    fn records_are_quantised_and_saturate() {
+        let mut w = Writer::init();
+        w.string("naïve");
+        w.position(1.3, -40000.);
+        w.velocity(-0.4, 1e9);
+        w.angle(-std::f64::consts::PI);
+        assert_eq!(
+            w.into_bytes(),
+            [
+                &[6][..],
+                "naïve".as_bytes(),
+                &5i16.to_le_bytes(),
+                &i16::MIN.to_le_bytes(),
+                &0i16.to_le_bytes(),
+                &i16::MAX.to_le_bytes(),
+                &32768u16.to_le_bytes(),
+            ]
+            .concat()
+        );
+
+        // long strings are cut on a character boundary
+        let mut w = Writer::init();
+        w.string(&"é".repeat(200));
+        assert_eq!(w.into_bytes()[0], 254);
+    }
+
+    #[test]
+    fn the_same_world_encodes_to_the_same_bytes() {
+        let (a, b) = (state(), state());
+        assert_eq!(a.snapshot(), b.snapshot());
+        assert_eq!(a.snapshot(), a.snapshot());
+    }
+
+    #[test]
+    fn deltas_bring_the_previous_snapshot_up_to_date() {
+        let mut state = state();
+        state.spawn_ship("leaving".to_string(), None, None);
+        let prev = state.frame();
+
+        state.remove_ship("leaving".to_string()).unwrap();
+        (0..40).for_each(|_| state.update());
+        let next = state.frame();
+
+        let mut decoded = decode_full(&prev.full());
+        let [spawned, changed, removed] = apply_delta(&mut decoded, &next.delta(&prev));
+        assert!(spawned > 0 && changed > 0 && removed > 0);
+
+        let expected = decode_full(&next.full());
+        assert_eq!(decoded.header, expected.header);
+        assert_eq!(decoded.sections, expected.sections);
+
+        // nothing happened, nothing to send
+        let [spawned, changed, removed] = apply_delta(&mut decoded, &next.delta(&next));
+        assert_eq!((spawned, changed, removed), (0, 0, 0));
+    }
+
+    #[test]
+    fn explosions_are_sent_once() {
+        let mut state = state();
+        let prev = decode_full(&state.snapshot());
+        state.update();
+        let next = decode_full(&state.snapshot());
+
+        let (prev, next) = (&prev.sections[3], &next.sections[3]);
+        let kept: Vec<_> = next.keys().filter(|k| prev.contains_key(*k)).collect();
+        assert!(!kept.is_empty());
+        assert!(kept.iter().all(|k| prev[*k] == next[*k]));
+    }
+
+    #[test]
+    fn counts_match_the_records_past_u16() {
+        let n = u16::MAX as usize + 10;
+        let mut section = Section::init();
+        (0..n as u64).for_each(|i| section.insert((0, i), |w| w.u8(1)));
+
+        let mut w = Writer::init();
+        section.write_full(&mut w);
+        let bytes = w.into_bytes();
+        let mut r = Reader { bytes: &bytes };
+        assert_eq!(r.count(), n);
+        assert_eq!(r.bytes.len(), n);
+
+        let mut w = Writer::init();
+        section.write_delta(&Section::init(), &mut w);
+        let bytes = w.into_bytes();
+        let mut r = Reader { bytes: &bytes };
+        assert_eq!(r.count(), n);
+        r.take(n);
+        assert_eq!((r.count(), r.count()), (0, 0));
+        assert!(r.bytes.is_empty());
+    }
+}
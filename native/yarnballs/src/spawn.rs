@@ -1,13 +1,16 @@
-use crate::enemy;
+use crate::arena::Arena;
+use crate::enemy::{self, bouncer, rock};
+use crate::rng::Pcg32;
+use serde::Deserialize;
 
 pub trait Spawner {
-    fn spawn(&self) -> enemy::Enemy;
+    fn spawn(&self, arena: &Arena, rng: &mut Pcg32, now: i64) -> enemy::Enemy;
     fn limit(&self) -> u32;
     fn interval(&self) -> u32;
 }
 
-#[derive(Default)]
-struct Bouncers {
+#[derive(Deserialize, Clone)]
+pub struct Bouncers {
     limit: u32,
     interval: u32,
     min_vel: Option<f64>,
@@ -15,8 +18,14 @@ struct Bouncers {
 }
 
 impl Spawner for Bouncers {
-    fn spawn(&self) -> enemy::Enemy {
-        enemy::Enemy::Bouncer(enemy::bouncer::spawn(self.min_vel, self.max_vel))
+    fn spawn(&self, arena: &Arena, rng: &mut Pcg32, now: i64) -> enemy::Enemy {
+        enemy::Enemy::Bouncer(enemy::bouncer::spawn(
+            self.min_vel,
+            self.max_vel,
+            arena,
+            rng,
+            now,
+        ))
     }
     fn limit(&self) -> u32 {
         self.limit
@@ -26,8 +35,8 @@ impl Spawner for Bouncers {
     }
 }
 
-#[derive(Default)]
-struct Rocks {
+#[derive(Deserialize, Clone)]
+pub struct Rocks {
     limit: u32,
     interval: u32,
     max_scale: Option<f64>,
@@ -36,11 +45,14 @@ struct Rocks {
 }
 
 impl Spawner for Rocks {
-    fn spawn(&self) -> enemy::Enemy {
+    fn spawn(&self, arena: &Arena, rng: &mut Pcg32, now: i64) -> enemy::Enemy {
         enemy::Enemy::Rock(enemy::rock::spawn(
             self.max_scale,
             self.min_vel,
             self.max_vel,
+            arena,
+            rng,
+            now,
         ))
     }
     fn limit(&self) -> u32 {
@@ -51,208 +63,55 @@ impl Spawner for Rocks {
     }
 }
 
-// Levels
-
-pub mod spawners {
-    use super::{Bouncers, Rocks, Spawner};
-
-    pub fn a_few_bouncers() -> Vec<Box<dyn Spawner>> {
-        vec![Box::new(Bouncers {
-            limit: 5,
-            interval: 1000,
-            ..Default::default()
-        })]
-    }
-
-    pub fn a_few_rocks() -> Vec<Box<dyn Spawner>> {
-        vec![Box::new(Rocks {
-            limit: 5,
-            interval: 1000,
-            ..Default::default()
-        })]
-    }
-
-    pub fn a_few_bouncers_and_rocks() -> Vec<Box<dyn Spawner>> {
-        let limit = 5;
-        let interval = 1000;
-        vec![
-            Box::new(Bouncers {
-                limit,
-                interval,
-                ..Default::default()
-            }),
-            Box::new(Rocks {
-                limit,
-                interval,
-                ..Default::default()
-            }),
-        ]
-    }
-
-    pub fn rocks() -> Vec<Box<dyn Spawner>> {
-        vec![Box::new(Rocks {
-            limit: 20,
-            interval: 500,
-            ..Default::default()
-        })]
-    }
-
-    pub fn bouncers_and_rocks() -> Vec<Box<dyn Spawner>> {
-        let limit = 20;
-        let interval = 500;
-        vec![
-            Box::new(Bouncers {
-                limit,
-                interval,
-                ..Default::default()
-            }),
-            Box::new(Rocks {
-                limit,
-                interval,
-                ..Default::default()
-            }),
-        ]
-    }
-
-    pub fn bigger_rocks() -> Vec<Box<dyn Spawner>> {
-        let limit = 15;
-        let interval = 500;
-        vec![Box::new(Rocks {
-            limit,
-            interval,
-            max_scale: Some(1.5),
-            ..Default::default()
-        })]
-    }
-
-    pub fn bouncers_and_bigger_rocks() -> Vec<Box<dyn Spawner>> {
-        let limit = 15;
-        let interval = 500;
-        vec![
-            Box::new(Bouncers {
-                limit,
-                interval,
-                ..Default::default()
-            }),
-            Box::new(Rocks {
-                limit,
-                interval,
-                max_scale: Some(1.5),
-                ..Default::default()
-            }),
-        ]
-    }
-
-    pub fn faster_rocks() -> Vec<Box<dyn Spawner>> {
-        let limit = 10;
-        let interval = 500;
-        vec![Box::new(Rocks {
-            limit,
-            interval,
-            min_vel: Some(150.),
-            max_vel: Some(200.),
-            ..Default::default()
-        })]
-    }
-
-    pub fn faster_rocks_and_bigger_rocks() -> Vec<Box<dyn Spawner>> {
-        let limit = 20;
-        let interval = 500;
-        vec![
-            Box::new(Rocks {
-                limit,
-                interval,
-                min_vel: Some(150.),
-                max_vel: Some(200.),
-                ..Default::default()
-            }),
-            Box::new(Rocks {
-                limit,
-                interval,
-                max_scale: Some(1.5),
-                ..Default::default()
-            }),
-        ]
-    }
+// A spawner as described in a level document, tagged by `kind`.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Config {
+    Bouncers(Bouncers),
+    Rocks(Rocks),
+}
 
-    pub fn bouncers_faster_rocks_and_bigger_rocks() -> Vec<Box<dyn Spawner>> {
-        let limit = 20;
-        let interval = 500;
-        vec![
-            Box::new(Bouncers {
-                limit,
-                interval,
-                ..Default::default()
-            }),
-            Box::new(Rocks {
-                limit,
-                interval,
-                min_vel: Some(150.),
-                max_vel: Some(200.),
-                ..Default::default()
-            }),
-            Box::new(Rocks {
-                limit,
-                interval,
-                max_scale: Some(1.5),
-                ..Default::default()
-            }),
-        ]
+impl Config {
+    // The range spawn velocities are drawn from, defaults included.
+    pub fn vel_range(&self) -> (f64, f64) {
+        match self {
+            Self::Bouncers(bouncers) => (
+                bouncers.min_vel.unwrap_or(bouncer::DEFAULT_MIN_VEL),
+                bouncers.max_vel.unwrap_or(bouncer::DEFAULT_MAX_VEL),
+            ),
+            Self::Rocks(rocks) => (
+                rocks.min_vel.unwrap_or(rock::DEFAULT_MIN_VEL),
+                rocks.max_vel.unwrap_or(rock::DEFAULT_MAX_VEL),
+            ),
+        }
+    }
+
+    // The largest scale spawned, defaults included, for spawners that scale their enemies.
+    pub fn max_scale(&self) -> Option<f64> {
+        match self {
+            Self::Bouncers(_) => None,
+            Self::Rocks(rocks) => Some(rocks.max_scale.unwrap_or(rock::DEFAULT_SCALE)),
+        }
     }
+}
 
-    pub fn madness() -> Vec<Box<dyn Spawner>> {
-        let limit = 50;
-        let interval = 250;
-        vec![
-            Box::new(Bouncers {
-                limit,
-                interval,
-                min_vel: Some(100.),
-                max_vel: Some(150.),
-                ..Default::default()
-            }),
-            Box::new(Rocks {
-                limit,
-                interval,
-                min_vel: Some(200.),
-                max_vel: Some(250.),
-                max_scale: Some(1.5),
-                ..Default::default()
-            }),
-        ]
+impl Spawner for Config {
+    fn spawn(&self, arena: &Arena, rng: &mut Pcg32, now: i64) -> enemy::Enemy {
+        match self {
+            Self::Bouncers(bouncers) => bouncers.spawn(arena, rng, now),
+            Self::Rocks(rocks) => rocks.spawn(arena, rng, now),
+        }
     }
-
-    pub fn overkill() -> Vec<Box<dyn Spawner>> {
-        let limit = 500;
-        let interval = 50;
-        vec![
-            Box::new(Bouncers {
-                limit,
-                interval,
-                min_vel: Some(100.),
-                max_vel: Some(150.),
-                ..Default::default()
-            }),
-            Box::new(Rocks {
-                limit,
-                interval,
-                min_vel: Some(200.),
-                max_vel: Some(250.),
-                max_scale: Some(1.5),
-                ..Default::default()
-            }),
-        ]
+    fn limit(&self) -> u32 {
+        match self {
+            Self::Bouncers(bouncers) => bouncers.limit(),
+            Self::Rocks(rocks) => rocks.limit(),
+        }
     }
-
-    pub fn overbounce() -> Vec<Box<dyn Spawner>> {
-        let limit = 500;
-        let interval = 50;
-        vec![Box::new(Bouncers {
-            limit,
-            interval,
-            min_vel: Some(200.),
-            max_vel: Some(250.),
-            ..Default::default()
-        })]
+    fn interval(&self) -> u32 {
+        match self {
+            Self::Bouncers(bouncers) => bouncers.interval(),
+            Self::Rocks(rocks) => rocks.interval(),
+        }
     }
 }
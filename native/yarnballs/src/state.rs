@@ -1,23 +1,120 @@
-use crate::collision;
-use crate::spawn;
-use crate::{enemy::Enemies, missile, missile::Missiles, ship::Ships};
-use rustler::NifStruct;
-use std::collections::HashSet;
-
-#[derive(NifStruct)]
-#[module = "Yarnballs.State"]
+use crate::arena::Arena;
+use crate::clock::{self, Clock};
+use crate::collision::{self, Grid};
+use crate::config::Config;
+use crate::error::Error;
+use crate::event::{Event, Events};
+use crate::level::{self, Level};
+use crate::power_up::PowerUps;
+use crate::replay::{Command, Recording};
+use crate::rng::Pcg32;
+use crate::snapshot;
+use crate::spawn::Spawner;
+use crate::weapon::Weapon;
+use crate::{enemy::Enemies, missile::Missiles, ship, ship::Ships};
+use rand::{seq::SliceRandom, Rng};
+#[cfg(feature = "nif")]
+use rustler::{Atom, Encoder, Env, Term};
+use std::f64::consts::TAU;
+
+#[cfg(feature = "nif")]
+mod atoms {
+    rustler::atoms! {
+        atom_struct = "__struct__",
+        missiles,
+        enemies,
+        ships,
+        power_ups,
+        level,
+        score,
+        start_level_score,
+        next_level_score,
+        width,
+        height,
+    }
+}
+
 pub struct State {
+    clock: Clock,
+    rng: Pcg32,
+    arena: Arena,
+    grid: Grid,
     missiles: Missiles,
     enemies: Enemies,
     ships: Ships,
+    power_ups: PowerUps,
+    recording: Option<Recording>,
+    levels: Vec<Level>,
+    // ships pass through each other when absent
+    ship_bounce: Option<f64>,
+    // whether missiles hit the ships of other players
+    pvp: bool,
+    // and whether they hit teammates too
+    friendly_fire: bool,
+    // how likely a destroyed enemy is to leave a power-up behind
+    power_up_chance: f64,
+    events: Events,
 }
 
 impl State {
-    pub fn init() -> Self {
-        Self {
+    pub fn init(config: Config) -> Result<Self, Error> {
+        let config = config.seeded();
+        let arena = config.arena()?;
+        let levels = match &config.levels {
+            Some(document) => level::parse(document)?,
+            None => level::defaults(),
+        };
+
+        Ok(Self {
+            clock: Clock::init(),
+            rng: Pcg32::seed(config.seed.unwrap_or_default()),
+            arena,
+            grid: Grid::init(arena, config.cell_size)?,
             missiles: Missiles::init(),
             enemies: Enemies::init(),
             ships: Ships::init(),
+            power_ups: PowerUps::init(),
+            recording: None,
+            levels,
+            ship_bounce: config.ship_bounce()?,
+            pvp: config.pvp.unwrap_or_default(),
+            friendly_fire: config.friendly_fire.unwrap_or_default(),
+            power_up_chance: config.power_up_chance()?,
+            events: Events::init(),
+        })
+    }
+
+    // Initialize a state that records every command applied to it, for later replay.
+    pub fn init_recorded(config: Config) -> Result<Self, Error> {
+        let config = config.seeded();
+        Ok(Self {
+            recording: Some(Recording::init(config.clone())),
+            ..Self::init(config)?
+        })
+    }
+
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    pub fn apply(&mut self, command: Command) -> Result<(), Error> {
+        match command {
+            Command::SpawnShip { id, name, team } => self.spawn_ship(id, name, team),
+            Command::TurnShip { id, clockwise } => self.turn_ship(id, clockwise)?,
+            Command::ThrustShip { id } => self.thrust_ship(id)?,
+            Command::FireMissileOrRespawn { id } => self.fire_missile_or_respawn(id)?,
+            Command::RemoveShip { id } => self.remove_ship(id)?,
+            Command::SwitchWeapon { id, weapon } => self.switch_weapon(id, weapon)?,
+            Command::UpdateBodies { dt } => self.update_by(dt),
+        }
+        Ok(())
+    }
+
+    // Only commands that were applied successfully are recorded.
+    fn record(&mut self, command: Command) {
+        let at = self.clock.now();
+        if let Some(recording) = &mut self.recording {
+            recording.push(at, command);
         }
     }
 
@@ -25,136 +122,887 @@ impl State {
         self.ships.total_score()
     }
 
-    pub fn spawn_ship(&mut self, id: String, name: Option<String>) {
-        self.ships.spawn(id, name);
+    pub fn team_score(&self, team: &ship::Team) -> i64 {
+        self.ships.team_score(team)
     }
 
-    pub fn turn_ship(&mut self, id: String, clockwise: bool) {
-        self.ships.turn(id, clockwise);
+    pub fn spawn_ship(&mut self, id: String, name: Option<String>, team: Option<ship::Team>) {
+        let now = self.clock.now();
+        self.ships
+            .spawn(id.clone(), name.clone(), team.clone(), now);
+        self.record(Command::SpawnShip { id, name, team });
     }
 
-    pub fn thrust_ship(&mut self, id: String) {
-        self.ships.thrust(id);
+    pub fn turn_ship(&mut self, id: String, clockwise: bool) -> Result<(), Error> {
+        self.ships.turn(&id, clockwise)?;
+        self.record(Command::TurnShip { id, clockwise });
+        Ok(())
     }
 
-    pub fn remove_ship(&mut self, id: &String) {
-        self.ships.remove(id);
+    pub fn thrust_ship(&mut self, id: String) -> Result<(), Error> {
+        self.ships.thrust(&id, self.clock.now())?;
+        self.record(Command::ThrustShip { id });
+        Ok(())
     }
 
-    pub fn fire_missile_or_respawn(&mut self, id: String) {
-        match self.ships.is_dead(&id) {
-            Some(false) => match self.ships.spawn_missile(&id) {
-                Some(missile) => self.missiles.add(missile),
-                None => (),
-            },
-            Some(true) => self.ships.respawn(id.clone()),
-            None => (),
+    pub fn remove_ship(&mut self, id: String) -> Result<(), Error> {
+        self.ships.remove(&id)?;
+        self.record(Command::RemoveShip { id });
+        Ok(())
+    }
+
+    pub fn fire_missile_or_respawn(&mut self, id: String) -> Result<(), Error> {
+        if self.ships.is_dead(&id)? {
+            self.ships.respawn(&id, self.clock.now())?;
+        } else {
+            self.ships
+                .fire(&id, &mut self.rng, self.clock.now())?
+                .into_iter()
+                .for_each(|m| self.missiles.add(m));
         }
+        self.record(Command::FireMissileOrRespawn { id });
+        Ok(())
+    }
+
+    pub fn switch_weapon(&mut self, id: String, weapon: Weapon) -> Result<(), Error> {
+        self.ships.switch_weapon(&id, weapon)?;
+        self.record(Command::SwitchWeapon { id, weapon });
+        Ok(())
+    }
+
+    pub fn frame(&self) -> snapshot::Frame {
+        let (level, (start_level_score, next_level_score)) =
+            (self.level(), self.next_level_score());
+
+        let mut header = snapshot::Writer::init();
+        header.i64(self.clock.now());
+        header.u16(level as u16);
+        header.i64(self.total_score());
+        header.u32(start_level_score);
+        header.u32(next_level_score.unwrap_or(u32::MAX));
+        header.u16(self.arena.width as u16);
+        header.u16(self.arena.height as u16);
+
+        let mut frame = snapshot::Frame {
+            header: header.into_bytes(),
+            ..snapshot::Frame::empty()
+        };
+        self.ships.frame(&mut frame.ships);
+        self.missiles.frame(&mut frame.missiles);
+        self.enemies
+            .frame(&mut frame.enemies, &mut frame.explosions);
+        self.power_ups.frame(&mut frame.power_ups);
+        frame
+    }
+
+    pub fn snapshot(&self) -> Vec<u8> {
+        self.frame().full()
     }
 
     pub fn level(&self) -> u32 {
-        self.level_with_spawner().0
+        self.current_level().index
     }
 
     pub fn next_level_score(&self) -> (u32, Option<u32>) {
-        self.level_with_spawner().1
-    }
-
-    // TODO: Define a propery level struct. Stop repeating hard-coding level score goals.
-    fn level_with_spawner(&self) -> (u32, (u32, Option<u32>), Vec<Box<dyn spawn::Spawner>>) {
-        match self.total_score() {
-            x if x < 15 => (0, (0, Some(15)), spawn::spawners::a_few_bouncers()),
-            x if x < 30 => (1, (15, Some(30)), spawn::spawners::a_few_rocks()),
-            x if x < 60 => (
-                2,
-                (30, Some(60)),
-                spawn::spawners::a_few_bouncers_and_rocks(),
-            ),
-            x if x < 90 => (3, (60, Some(90)), spawn::spawners::rocks()),
-            x if x < 120 => (4, (90, Some(120)), spawn::spawners::bouncers_and_rocks()),
-            x if x < 200 => (5, (120, Some(200)), spawn::spawners::bigger_rocks()),
-            x if x < 280 => (
-                6,
-                (200, Some(280)),
-                spawn::spawners::bouncers_and_bigger_rocks(),
-            ),
-            x if x < 330 => (7, (280, Some(330)), spawn::spawners::faster_rocks()),
-            x if x < 430 => (
-                8,
-                (330, Some(430)),
-                spawn::spawners::faster_rocks_and_bigger_rocks(),
-            ),
-            x if x < 1000 => (
-                9,
-                (430, Some(1000)),
-                spawn::spawners::bouncers_faster_rocks_and_bigger_rocks(),
-            ),
-            // TODO: these levels aren't supposed to be beatable
-            x if x < 2000 => (10, (1000, Some(2000)), spawn::spawners::madness()),
-            // TODO: this level has noticeable lag
-            x if x < 5000 => (11, (2000, Some(5000)), spawn::spawners::overkill()),
-            _ => (12, (5000, None), spawn::spawners::overbounce()),
+        let level = self.current_level();
+        (level.min_score, level.max_score)
+    }
+
+    // Teams go through the same levels on their own score. Enemies still spawn by the level
+    // everyone reached together.
+    pub fn team_level(&self, team: &ship::Team) -> u32 {
+        self.team_current_level(team).index
+    }
+
+    pub fn team_next_level_score(&self, team: &ship::Team) -> (u32, Option<u32>) {
+        let level = self.team_current_level(team);
+        (level.min_score, level.max_score)
+    }
+
+    fn team_current_level(&self, team: &ship::Team) -> &Level {
+        level::current(&self.levels, self.team_score(team))
+    }
+
+    fn current_level(&self) -> &Level {
+        level::current(&self.levels, self.total_score())
+    }
+
+    /*
+    Scatter `ships` ships, `missiles` missiles and `enemies` enemies across the arena, to build
+    worlds of a given size for benchmarks. The first ship is credited with `score`, and enemies
+    are drawn from the spawners of the level that score reaches.
+    */
+    pub fn populate(&mut self, ships: usize, missiles: usize, enemies: usize, score: i64) {
+        let now = self.clock.now();
+        let (width, height) = (self.arena.width, self.arena.height);
+        let scatter = |rng: &mut Pcg32| {
+            (
+                rng.gen_range(0. ..width),
+                rng.gen_range(0. ..height),
+                rng.gen_range(0. ..TAU),
+            )
+        };
+
+        let ids: Vec<ship::ID> = (0..ships).map(|i| format!("ship-{}", i)).collect();
+        for id in &ids {
+            self.ships.spawn(id.clone(), None, None, now);
+        }
+        if let Some(id) = ids.first() {
+            self.ships.increase_score(id.clone(), score);
+        }
+
+        // missiles are fired by ships, so move a ship to where each missile should start
+        for id in ids
+            .iter()
+            .cycle()
+            .take(if ships > 0 { missiles } else { 0 })
+        {
+            let (x, y, angle) = scatter(&mut self.rng);
+            self.ships.place(id, x, y, angle).ok();
+            if let Ok(missiles) = self.ships.spawn_missiles(id, &mut self.rng, now) {
+                missiles.into_iter().for_each(|m| self.missiles.add(m));
+            }
+        }
+        for id in &ids {
+            let (x, y, angle) = scatter(&mut self.rng);
+            self.ships.place(id, x, y, angle).ok();
+        }
+
+        let spawners = &level::current(&self.levels, self.ships.total_score()).spawners;
+        for _ in 0..enemies {
+            if let Some(spawner) = spawners.choose(&mut self.rng) {
+                let mut enemy = spawner.spawn(&self.arena, &mut self.rng, now);
+                let (x, y, _) = scatter(&mut self.rng);
+                enemy.place(x, y);
+                self.enemies.insert(enemy);
+            }
         }
     }
 
     pub fn update(&mut self) {
-        self.missiles.update();
-        self.enemies.update();
-        self.ships.update();
+        self.update_by(clock::STEP);
+    }
+
+    /*
+    Advance the simulation by a caller-supplied amount of time, in milliseconds.
+
+    Bodies move and collide in fixed steps, as many as fit in the time accumulated so far. What
+    is left over carries over to the next update.
+    */
+    pub fn update_by(&mut self, dt: i64) {
+        self.clock.accumulate(dt);
+        while self.clock.step() {
+            self.update_bodies();
+        }
+        self.record(Command::UpdateBodies { dt });
+    }
+
+    fn update_bodies(&mut self) {
+        let now = self.clock.now();
+
+        self.missiles.update(&self.enemies, now);
+        let destroyed = self.enemies.update(&self.arena, &mut self.rng, now);
+        for center in destroyed {
+            self.power_ups
+                .drop_at(center, self.power_up_chance, &mut self.rng, now);
+        }
+        self.power_ups.update(now);
+        self.ships.update(&self.arena, now);
 
         self.update_collisions();
         self.spawn_enemies();
     }
 
     fn spawn_enemies(&mut self) {
-        self.enemies.spawn(self.level_with_spawner().2);
+        let level = level::current(&self.levels, self.ships.total_score());
+        self.enemies.spawn(
+            &level.spawners,
+            &self.arena,
+            &mut self.rng,
+            self.clock.now(),
+        );
     }
 
-    pub fn update_collisions<'a>(&mut self) {
-        // Initialize spatial hash with collidable entities
-        let mut sh = collision::new_spatial_hash::<'a>();
-        for m in &mut self.missiles.entities {
-            sh.insert_missile(m);
-        }
-        for e in &mut self.enemies.entities() {
-            sh.insert_enemy(e);
-        }
-        for e in &mut self.ships.entities() {
-            sh.insert_ship(e);
-        }
+    pub fn update_collisions(&mut self) {
+        // the grid is moved out while the collisions it found are applied
+        let mut grid = std::mem::take(&mut self.grid);
+        grid.clear();
+        self.missiles
+            .entities
+            .iter()
+            .for_each(|m| grid.insert_missile(m));
+        self.enemies.entities().for_each(|e| grid.insert_enemy(e));
+        self.ships.entities().for_each(|s| grid.insert_ship(s));
+        self.power_ups
+            .entities
+            .iter()
+            .for_each(|p| grid.insert_power_up(p));
+        grid.collide();
 
-        // Apply enemy-missile and ship-enemy collisions
-        let mut enemy_missile_collisions: HashSet<collision::EnemyMissileCollision> =
-            HashSet::new();
-        let mut ship_enemy_collisions: HashSet<collision::ShipEnemyCollision> = HashSet::new();
-        sh.spaces().for_each(|s| {
-            enemy_missile_collisions.extend(s.enemy_missile_collisions());
-            ship_enemy_collisions.extend(s.ship_enemy_collisions());
-        });
-        self.apply_enemy_missile_collisions(
-            enemy_missile_collisions.into_iter().collect::<Vec<_>>(),
-        );
-        self.apply_ship_enemy_collisions(ship_enemy_collisions.into_iter().collect::<Vec<_>>());
+        self.apply_enemy_missile_collisions(grid.enemy_missile_collisions());
+        self.apply_ship_enemy_collisions(grid.ship_enemy_collisions());
+        self.apply_ship_ship_collisions(grid.ship_ship_collisions());
+        self.apply_ship_missile_collisions(grid.ship_missile_collisions());
+        self.apply_ship_power_up_collisions(grid.ship_power_up_collisions());
+        self.grid = grid;
     }
 
-    pub fn apply_enemy_missile_collisions(&mut self, emcs: Vec<collision::EnemyMissileCollision>) {
+    pub fn apply_enemy_missile_collisions(&mut self, emcs: &[collision::EnemyMissileCollision]) {
         // apply entity-specific collision effects
-        self.missiles.apply_enemy_collisions(&emcs);
-        self.enemies.apply_missile_collisions(&emcs);
+        self.missiles.apply_enemy_collisions(emcs);
+        self.enemies.apply_missile_collisions(emcs);
 
         // increase scores
-        let ids: Vec<missile::ID> = emcs.into_iter().map(|(_, m)| m).collect();
         self.missiles
-            .shooter_counts(&ids)
+            .shooter_counts(emcs)
             .into_iter()
             .for_each(|(id, pts)| self.ships.increase_score(id, pts));
     }
 
-    pub fn apply_ship_enemy_collisions(&mut self, secs: Vec<collision::ShipEnemyCollision>) {
-        secs.into_iter()
-            .for_each(|(sid, eid)| match self.enemies.entities.get(&eid) {
+    pub fn apply_ship_enemy_collisions(&mut self, secs: &[collision::ShipEnemyCollision]) {
+        let now = self.clock.now();
+        secs.iter()
+            .for_each(|(sid, eid)| match self.enemies.entities.get(eid) {
                 None => (),
-                Some(enemy) => self.ships.collide_with(sid, enemy),
+                Some(enemy) => self.ships.collide_with(sid, enemy, &self.arena, now),
             });
     }
+
+    pub fn apply_ship_ship_collisions(&mut self, sscs: &[collision::ShipShipCollision]) {
+        if let Some(bounce) = self.ship_bounce {
+            sscs.iter()
+                .for_each(|(id1, id2)| self.ships.bump(id1, id2, &self.arena, bounce));
+        }
+    }
+
+    /*
+    Missiles are spent on the first thing they hit, unless they pierce, and never hit their own
+    shooter. They pass through teammates unless friendly fire is on, and shooting a teammate down
+    scores nothing.
+    */
+    pub fn apply_ship_missile_collisions(&mut self, smcs: &[collision::ShipMissileCollision]) {
+        if !self.pvp {
+            return;
+        }
+
+        for (sid, mid) in smcs {
+            let (shooter_id, damage) = match self.missiles.hitting(mid, sid) {
+                Some(m) if m.shooter_id() != sid => (m.shooter_id().clone(), m.damage()),
+                _ => continue,
+            };
+            let teammates = self.ships.are_teammates(sid, &shooter_id);
+            if teammates && !self.friendly_fire {
+                continue;
+            }
+            self.missiles.spend(mid, sid);
+
+            if self.ships.hit(sid, damage) {
+                if !teammates {
+                    self.ships
+                        .increase_score(shooter_id.clone(), ship::KILL_SCORE);
+                }
+                self.events.push(Event::Kill {
+                    killer_id: shooter_id,
+                    victim_id: sid.clone(),
+                    at: self.clock.now(),
+                });
+            }
+        }
+    }
+
+    // A power-up goes to a ship that touches it and can take it. Collisions come sorted by ship
+    // ID, so when several ships touch it in the same tick the smallest ID gets it, whichever got
+    // there first.
+    pub fn apply_ship_power_up_collisions(&mut self, spcs: &[collision::ShipPowerUpCollision]) {
+        let now = self.clock.now();
+        for (sid, pid) in spcs {
+            if !self.ships.can_power_up(sid) {
+                continue;
+            }
+            if let Some(kind) = self.power_ups.pick_up(pid) {
+                self.ships.power_up(sid, kind, now);
+            }
+        }
+    }
+
+    // Hand over the events that happened since the last call, oldest first.
+    pub fn drain_events(&mut self) -> Vec<Event> {
+        self.events.drain()
+    }
+}
+
+// Only what clients render is encoded, as a `Yarnballs.State` struct.
+#[cfg(feature = "nif")]
+impl Encoder for State {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let (level, (start_level_score, next_level_score)) =
+            (self.level(), self.next_level_score());
+        let module = Atom::from_str(env, "Elixir.Yarnballs.State").unwrap();
+
+        let keys = [
+            atoms::atom_struct(),
+            atoms::missiles(),
+            atoms::enemies(),
+            atoms::ships(),
+            atoms::power_ups(),
+            atoms::level(),
+            atoms::score(),
+            atoms::start_level_score(),
+            atoms::next_level_score(),
+            atoms::width(),
+            atoms::height(),
+        ]
+        .map(|k| k.encode(env));
+        let values = [
+            module.encode(env),
+            self.missiles.encode(env),
+            self.enemies.encode(env),
+            self.ships.encode(env),
+            self.power_ups.encode(env),
+            level.encode(env),
+            self.total_score().encode(env),
+            start_level_score.encode(env),
+            next_level_score.encode(env),
+            self.arena.width.encode(env),
+            self.arena.height.encode(env),
+        ];
+        Term::map_from_term_arrays(env, &keys, &values).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::Circle;
+    use crate::effect;
+    use crate::enemy::{bouncer, Enemy};
+    use crate::power_up::{Kind, PowerUp};
+    use proptest::prelude::*;
+    use std::collections::{HashMap, HashSet};
+
+    fn config(seed: u64) -> Config {
+        Config {
+            seed: Some(seed),
+            ..Config::default()
+        }
+    }
+
+    fn state(seed: u64) -> State {
+        State::init(config(seed)).unwrap()
+    }
+
+    // Put a bouncer right on top of the newest missile.
+    fn target_newest_missile(state: &mut State) {
+        let (x, y) = state.missiles.entities.last().unwrap().center();
+        let mut enemy = Enemy::Bouncer(bouncer::spawn(None, None, &state.arena, &mut state.rng, 0));
+        let r = enemy.radius();
+        enemy.place(x - r, y - r);
+        state.enemies.insert(enemy);
+    }
+
+    const ROCKS: &str = r#"
+[[levels]]
+score = 0
+spawners = [
+    { kind = "rocks", limit = 20, interval = 50, max_scale = 1.5 },
+    { kind = "bouncers", limit = 10, interval = 50 },
+]
+
+[[levels]]
+score = 3
+spawners = [
+    { kind = "rocks", limit = 5, interval = 100, min_vel = 200.0, max_vel = 400.0, max_scale = 1.5 },
+]
+"#;
+
+    // Commands for a handful of ships, some of which never spawn. Most of them let time pass.
+    fn command() -> impl Strategy<Value = Command> {
+        let id = || (0..4).prop_map(|i| format!("ship-{}", i));
+        prop_oneof![
+            1 => (id(), proptest::option::of("[a-z]{1,8}"), proptest::option::of("red|blue"))
+                .prop_map(|(id, name, team)| Command::SpawnShip { id, name, team }),
+            2 => (id(), any::<bool>())
+                .prop_map(|(id, clockwise)| Command::TurnShip { id, clockwise }),
+            2 => id().prop_map(|id| Command::ThrustShip { id }),
+            3 => id().prop_map(|id| Command::FireMissileOrRespawn { id }),
+            1 => id().prop_map(|id| Command::RemoveShip { id }),
+            1 => (id(), weapon())
+                .prop_map(|(id, weapon)| Command::SwitchWeapon { id, weapon }),
+            8 => prop_oneof![9 => 0..100i64, 1 => 0..100_000i64]
+                .prop_map(|dt| Command::UpdateBodies { dt }),
+        ]
+    }
+
+    fn weapon() -> impl Strategy<Value = Weapon> {
+        proptest::sample::select(vec![
+            Weapon::Single,
+            Weapon::Spread,
+            Weapon::Rapid,
+            Weapon::Laser,
+            Weapon::Homing,
+        ])
+    }
+
+    fn finite((x, y): (f64, f64)) -> bool {
+        x.is_finite() && y.is_finite()
+    }
+
+    fn check_entities(state: &State, spawned: &HashSet<ship::ID>) -> Result<(), TestCaseError> {
+        for ship in state.ships.entities() {
+            prop_assert!((0. ..=ship::MAX_HEALTH).contains(&ship.health()));
+            prop_assert!(ship.score() >= 0);
+            prop_assert!(finite(ship.center()));
+        }
+        for missile in &state.missiles.entities {
+            prop_assert!(spawned.contains(missile.shooter_id()));
+            prop_assert!(finite(missile.center()));
+        }
+        for enemy in state.enemies.entities() {
+            prop_assert!(finite(enemy.center()));
+        }
+        for explosion in state.enemies.explosions().entities() {
+            prop_assert!(finite(explosion.position()));
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn commands_keep_the_world_sound(
+            seed in any::<u64>(),
+            rocks in any::<bool>(),
+            ship_bounce in proptest::option::of(0. ..=1f64),
+            pvp in proptest::option::of(any::<bool>()),
+            commands in prop::collection::vec(command(), 0..300),
+        ) {
+            let mut state = State::init(Config {
+                levels: rocks.then(|| ROCKS.to_string()),
+                ship_bounce,
+                pvp,
+                ..config(seed)
+            })
+            .unwrap();
+            let mut spawned = HashSet::new();
+
+            for command in commands {
+                if let Command::SpawnShip { id, .. } = &command {
+                    spawned.insert(id.clone());
+                }
+                // destroyed rocks that split are the only way past the spawner limit
+                let before = state.enemies.count() as usize;
+                let splitting = state.enemies.entities().filter(|e| e.splits()).count();
+
+                state.apply(command).ok();
+
+                let limit = state
+                    .current_level()
+                    .spawners
+                    .iter()
+                    .map(|s| s.limit() as usize)
+                    .max()
+                    .unwrap_or_default();
+                prop_assert!(state.enemies.count() as usize <= limit.max(before + 3 * splitting));
+                check_entities(&state, &spawned)?;
+            }
+        }
+    }
+
+    #[test]
+    fn hits_score_a_point_each() {
+        let mut state = state(1);
+        state.spawn_ship("a".to_string(), None, None);
+        state.spawn_ship("b".to_string(), None, None);
+        state.ships.place(&"b".to_string(), 300., 300., 0.).unwrap();
+
+        state.fire_missile_or_respawn("a".to_string()).unwrap();
+        target_newest_missile(&mut state);
+        state.fire_missile_or_respawn("b".to_string()).unwrap();
+        target_newest_missile(&mut state);
+        state.update_collisions();
+        assert_eq!(state.total_score(), 2);
+
+        // the enemies blow up and the missiles are spent on the next tick
+        state.update();
+        assert_eq!(state.total_score(), 2);
+        assert_eq!(state.enemies.count(), 0);
+        assert!(state.missiles.entities.is_empty());
+    }
+
+    // Ship "a" shooting down an enemy, in a room where power-ups drop with `chance`.
+    fn shot_down(chance: f64) -> State {
+        let mut state = State::init(Config {
+            power_up_chance: Some(chance),
+            ..config(1)
+        })
+        .unwrap();
+        state.spawn_ship("a".to_string(), None, None);
+        state.fire_missile_or_respawn("a".to_string()).unwrap();
+        target_newest_missile(&mut state);
+        state.update_collisions();
+        state.update();
+        state
+    }
+
+    #[test]
+    fn destroyed_enemies_may_drop_power_ups() {
+        assert_eq!(shot_down(1.).power_ups.entities.len(), 1);
+        assert!(shot_down(0.).power_ups.entities.is_empty());
+
+        let chance = |chance| {
+            State::init(Config {
+                power_up_chance: Some(chance),
+                ..config(1)
+            })
+        };
+        assert!(matches!(chance(-0.5), Err(Error::InvalidPowerUpChance(c)) if c == -0.5));
+    }
+
+    #[test]
+    fn ships_touching_a_power_up_together_leave_it_to_the_smallest_id() {
+        let mut state = state(1);
+        let power_up = PowerUp::spawn(Kind::ScoreMultiplier, 300., 200., &mut state.rng, 0);
+        let (x, y) = power_up.center();
+        state.power_ups.entities.push(power_up);
+        // "c" joins first, but both touch it in the same tick
+        for id in ["c", "b"] {
+            let id = id.to_string();
+            state.spawn_ship(id.clone(), None, None);
+            state.ships.place(&id, x - 45., y - 45., 0.).unwrap();
+        }
+        state.update();
+        // picked up power-ups are gone on the next tick
+        state.update();
+        assert!(state.power_ups.entities.is_empty());
+
+        state.ships.increase_score("b".to_string(), 10);
+        state.ships.increase_score("c".to_string(), 10);
+        let score = |id: &str| state.ships.entities().find(|s| s.id == id).unwrap().score();
+        assert_eq!((score("b"), score("c")), (20, 10));
+    }
+
+    #[test]
+    fn ships_going_down_leave_power_ups_for_others() {
+        let mut state = state(1);
+        let power_up = PowerUp::spawn(Kind::ScoreMultiplier, 300., 200., &mut state.rng, 0);
+        let pid = power_up.id;
+        state.power_ups.entities.push(power_up);
+        let (b, c) = ("b".to_string(), "c".to_string());
+        state.spawn_ship(b.clone(), None, None);
+        assert!(state.ships.hit(&b, f64::INFINITY));
+
+        state.apply_ship_power_up_collisions(&[(b.clone(), pid)]);
+        state.update();
+        assert_eq!(state.power_ups.entities.len(), 1);
+
+        state.spawn_ship(c.clone(), None, None);
+        state.apply_ship_power_up_collisions(&[(b, pid), (c.clone(), pid)]);
+        state.update();
+        assert!(state.power_ups.entities.is_empty());
+        state.ships.increase_score(c.clone(), 10);
+        assert_eq!(
+            state.ships.entities().find(|s| s.id == c).unwrap().score(),
+            20
+        );
+    }
+
+    #[test]
+    fn missing_scores_nothing() {
+        let mut state = state(1);
+        state.spawn_ship("a".to_string(), None, None);
+        state.fire_missile_or_respawn("a".to_string()).unwrap();
+        state.update_by(16);
+        assert_eq!(state.total_score(), 0);
+    }
+
+    // A ship thrusting and turning from the middle of the arena, after ticks of the given lengths.
+    fn flown(ticks: &[i64]) -> ((f64, f64), f64) {
+        let mut state = state(1);
+        let id = "a".to_string();
+        state.spawn_ship(id.clone(), None, None);
+        state.ships.place(&id, 300., 200., 0.).unwrap();
+        for _ in 0..5 {
+            state.thrust_ship(id.clone()).unwrap();
+            state.turn_ship(id.clone(), true).unwrap();
+        }
+        ticks.iter().for_each(|&dt| state.update_by(dt));
+        let ship = state.ships.entities().next().unwrap();
+        (ship.center(), ship.angle())
+    }
+
+    #[test]
+    fn ships_fly_the_same_whatever_the_tick_rate() {
+        let steady = flown(&[16; 20]);
+        assert_eq!(flown(&[5, 11, 30, 2, 80, 36, 8, 148]), steady);
+        assert_eq!(flown(&[160, 160]), steady);
+        assert_ne!(flown(&[16; 19]), steady);
+    }
+
+    // Where two ships that were spawned on top of each other are after a tick.
+    fn spawned_together(ship_bounce: Option<f64>) -> Vec<(f64, f64)> {
+        let mut state = State::init(Config {
+            ship_bounce,
+            ..config(1)
+        })
+        .unwrap();
+        state.spawn_ship("a".to_string(), None, None);
+        state.spawn_ship("b".to_string(), None, None);
+        state.update();
+        let mut centers: Vec<(f64, f64)> = state.ships.entities().map(|s| s.center()).collect();
+        centers.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        centers
+    }
+
+    #[test]
+    fn ships_bump_only_in_rooms_that_want_it() {
+        let passed = spawned_together(None);
+        assert_eq!(passed[0], passed[1]);
+        let bumped = spawned_together(Some(0.5));
+        assert_ne!(bumped[0], bumped[1]);
+
+        let bounce = |bounce| {
+            State::init(Config {
+                ship_bounce: Some(bounce),
+                ..config(1)
+            })
+        };
+        assert!(matches!(bounce(1.5), Err(Error::InvalidShipBounce(b)) if b == 1.5));
+        assert!(matches!(bounce(f64::NAN), Err(Error::InvalidShipBounce(_))));
+    }
+
+    // Ship "a" shooting ship "b" point blank `shots` times, all at once.
+    fn duel(config: Config, teams: [Option<&str>; 2], shots: usize) -> State {
+        let mut state = State::init(config).unwrap();
+        let (a, b) = ("a".to_string(), "b".to_string());
+        let [team_a, team_b] = teams.map(|t| t.map(str::to_string));
+        state.spawn_ship(a.clone(), None, team_a);
+        state.spawn_ship(b.clone(), None, team_b);
+        state.ships.place(&a, 300., 200., 0.).unwrap();
+        for _ in 0..shots {
+            // firing as fast as the weapon allows would take a while
+            let missiles = state.ships.spawn_missiles(&a, &mut state.rng, 0).unwrap();
+            missiles.into_iter().for_each(|m| state.missiles.add(m));
+            let (x, y) = state.missiles.entities.last().unwrap().center();
+            state.ships.place(&b, x - 45., y - 45., 0.).unwrap();
+            state.update_collisions();
+        }
+        state
+    }
+
+    fn health(state: &State, id: &str) -> f64 {
+        state
+            .ships
+            .entities()
+            .find(|s| s.id == id)
+            .unwrap()
+            .health()
+    }
+
+    #[test]
+    fn missiles_hit_other_players_in_pvp() {
+        let pvp = || Config {
+            pvp: Some(true),
+            ..config(1)
+        };
+        let mut state = duel(pvp(), [None, None], 1);
+        assert_eq!(
+            health(&state, "b"),
+            ship::MAX_HEALTH - Weapon::Single.stats().damage
+        );
+        // the shooter sits right on its own missile
+        assert_eq!(health(&state, "a"), ship::MAX_HEALTH);
+        assert!(state.drain_events().is_empty());
+
+        let mut state = duel(pvp(), [None, None], 4);
+        assert_eq!(health(&state, "b"), 0.);
+        assert_eq!(state.total_score(), ship::KILL_SCORE);
+        let kill = Event::Kill {
+            killer_id: "a".to_string(),
+            victim_id: "b".to_string(),
+            at: 0,
+        };
+        assert_eq!(state.drain_events(), vec![kill]);
+        assert!(state.drain_events().is_empty());
+    }
+
+    #[test]
+    fn respawned_ships_cannot_be_hit_for_a_while() {
+        let pvp = Config {
+            pvp: Some(true),
+            ..config(1)
+        };
+        let mut state = duel(pvp, [None, None], 4);
+        state.update();
+        state.fire_missile_or_respawn("b".to_string()).unwrap();
+        assert_eq!(health(&state, "b"), ship::MAX_HEALTH);
+
+        let shoot = |state: &mut State| {
+            let a = "a".to_string();
+            let missiles = state.ships.spawn_missiles(&a, &mut state.rng, 0).unwrap();
+            missiles.into_iter().for_each(|m| state.missiles.add(m));
+            let (x, y) = state.missiles.entities.last().unwrap().center();
+            state
+                .ships
+                .place(&"b".to_string(), x - 45., y - 45., 0.)
+                .unwrap();
+            state.update_collisions();
+        };
+        shoot(&mut state);
+        assert_eq!(health(&state, "b"), ship::MAX_HEALTH);
+
+        (0..=effect::Kind::Invulnerable.duration() / clock::STEP).for_each(|_| state.update());
+        let healthy = health(&state, "b");
+        shoot(&mut state);
+        assert!(health(&state, "b") < healthy);
+    }
+
+    #[test]
+    fn missiles_pass_through_players_otherwise() {
+        let mut state = duel(config(1), [None, None], 4);
+        assert_eq!(health(&state, "b"), ship::MAX_HEALTH);
+        assert!(state.drain_events().is_empty());
+    }
+
+    #[test]
+    fn teammates_only_hit_each_other_with_friendly_fire() {
+        let pvp = |friendly_fire| Config {
+            pvp: Some(true),
+            friendly_fire,
+            ..config(1)
+        };
+
+        let state = duel(pvp(None), [Some("red"), Some("red")], 4);
+        assert_eq!(health(&state, "b"), ship::MAX_HEALTH);
+        let state = duel(pvp(None), [Some("red"), Some("blue")], 1);
+        assert_eq!(
+            health(&state, "b"),
+            ship::MAX_HEALTH - Weapon::Single.stats().damage
+        );
+
+        // a teamkill takes the ship down, but scores nothing
+        let mut state = duel(pvp(Some(true)), [Some("red"), Some("red")], 4);
+        assert_eq!(health(&state, "b"), 0.);
+        assert_eq!(state.total_score(), 0);
+        assert_eq!(state.drain_events().len(), 1);
+    }
+
+    #[test]
+    fn teams_level_up_on_their_own_score() {
+        let mut state = state(1);
+        for (id, team) in [("a", "red"), ("b", "red"), ("c", "blue")] {
+            state.spawn_ship(id.to_string(), None, Some(team.to_string()));
+        }
+        state.ships.increase_score("a".to_string(), 60);
+        state.ships.increase_score("b".to_string(), 50);
+        state.ships.increase_score("c".to_string(), 10);
+
+        let (red, blue) = ("red".to_string(), "blue".to_string());
+        assert_eq!((state.team_score(&red), state.team_score(&blue)), (110, 10));
+        // everyone together is further along than either team
+        assert!(state.team_level(&red) < state.level());
+        assert!(state.team_level(&blue) < state.team_level(&red));
+        assert_eq!(state.team_level(&blue), 0);
+        assert_eq!(state.team_next_level_score(&blue), (0, Some(15)));
+        assert_eq!(state.team_score(&"green".to_string()), 0);
+    }
+
+    #[test]
+    fn commands_for_unknown_ships_fail() {
+        let mut state = state(1);
+        let unknown = |result| matches!(result, Err(Error::UnknownShip(id)) if id == "a");
+        assert!(unknown(state.turn_ship("a".to_string(), true)));
+        assert!(unknown(state.thrust_ship("a".to_string())));
+        assert!(unknown(state.fire_missile_or_respawn("a".to_string())));
+        assert!(unknown(state.remove_ship("a".to_string())));
+        assert!(unknown(state.switch_weapon("a".to_string(), Weapon::Laser)));
+    }
+
+    #[test]
+    fn lasers_go_through_every_ship_once() {
+        let mut state = State::init(Config {
+            pvp: Some(true),
+            ..config(1)
+        })
+        .unwrap();
+        let (a, b) = ("a".to_string(), "b".to_string());
+        state.spawn_ship(a.clone(), None, None);
+        state.spawn_ship(b.clone(), None, None);
+        state.ships.place(&a, 300., 200., 0.).unwrap();
+        state.switch_weapon(a.clone(), Weapon::Laser).unwrap();
+        state.fire_missile_or_respawn(a).unwrap();
+
+        let (x, y) = state.missiles.entities[0].center();
+        state.ships.place(&b, x - 45., y - 45., 0.).unwrap();
+        state.update_collisions();
+        state.update_collisions();
+        let damage = Weapon::Laser.stats().damage;
+        assert_eq!(health(&state, "b"), ship::MAX_HEALTH - damage);
+        assert_eq!(state.missiles.entities.len(), 1);
+    }
+
+    #[test]
+    fn homing_missiles_turn_towards_the_nearest_enemy() {
+        let mut state = state(1);
+        let id = "a".to_string();
+        state.spawn_ship(id.clone(), None, None);
+        state.ships.place(&id, 100., 300., 0.).unwrap();
+        state.switch_weapon(id.clone(), Weapon::Homing).unwrap();
+        state.fire_missile_or_respawn(id).unwrap();
+        let (_, y) = state.missiles.entities[0].center();
+
+        // a rock well above where the missile is headed
+        state.populate(0, 0, 1, 0);
+        let rock = state.enemies.entities.values_mut().next().unwrap();
+        rock.place(300., 50.);
+        state.update_by(160);
+        let (_, homed_y) = state.missiles.entities[0].center();
+        assert!(homed_y < y - 10.);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        // Ships are also hit straight on between ticks, so that plenty go down, and only going
+        // down costs them points.
+        #[test]
+        fn scores_only_drop_when_ships_go_down(
+            seed in any::<u64>(),
+            ships in 1..8usize,
+            missiles in 0..100usize,
+            enemies in 0..100usize,
+            score in 0..200i64,
+            hits in prop::collection::vec((0..8usize, 0. ..150f64), 1..60),
+        ) {
+            let mut state = State::init(Config {
+                levels: Some(ROCKS.to_string()),
+                pvp: Some(true),
+                ..config(seed)
+            })
+            .unwrap();
+            state.populate(ships, missiles, enemies, score);
+            let scores = |state: &State| -> HashMap<ship::ID, (i64, bool)> {
+                state.ships.entities().map(|s| (s.id.clone(), (s.score(), s.is_dead()))).collect()
+            };
+            let mut before = scores(&state);
+            let mut deaths = 0;
+            for (ship, damage) in hits {
+                state.ships.hit(&format!("ship-{}", ship % ships), damage);
+                state.update();
+                let after = scores(&state);
+                for (id, &(score, dead)) in &after {
+                    let Some(&(prev, was_dead)) = before.get(id) else { continue };
+                    let penalty = if dead && !was_dead {
+                        deaths += 1;
+                        ship::DEATH_PENALTY
+                    } else {
+                        0
+                    };
+                    prop_assert!(score >= prev - penalty, "{}: {} -> {}", id, prev, score);
+                }
+                before = after;
+            }
+            prop_assert!(deaths <= ships);
+        }
+    }
 }
@@ -1,10 +1,7 @@
 // utilities and constants
-use chrono::prelude::Utc;
+use crate::rng::Pcg32;
+use rand::{Rng, RngCore};
 use std::f64::consts::PI;
-use uuid::Uuid;
-
-pub const WIDTH: f64 = 640.;
-pub const HEIGHT: f64 = 480.;
 
 /*
 Wrap entity around if the reach a position less then `0` or more than `limit`.
@@ -24,16 +21,17 @@ fn modulo(a: i64, b: i64) -> i64 {
     ((a % b) + b) % b
 }
 
-pub fn now_in_millis() -> i64 {
-    Utc::now().timestamp_millis()
-}
-
-pub fn new_uuid() -> String {
-    Uuid::new_v4().to_string()
+// Pick one of two values with equal odds.
+pub fn either<T>(rng: &mut Pcg32, a: T, b: T) -> T {
+    if rng.gen_bool(0.5) {
+        a
+    } else {
+        b
+    }
 }
 
-pub fn new_uuid_as_u64_pair() -> (u64, u64) {
-    Uuid::new_v4().as_u64_pair()
+pub fn new_uuid_as_u64_pair(rng: &mut Pcg32) -> (u64, u64) {
+    (rng.next_u64(), rng.next_u64())
 }
 
 pub fn repel_angel(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
@@ -42,3 +40,48 @@ pub fn repel_angel(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
 
     dy.atan2(dx) + PI
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn wrap_dim_wraps_past_either_edge() {
+        assert_eq!(wrap_dim(650., 640, 0.), 10.);
+        assert_eq!(wrap_dim(-10., 640, 0.), 630.);
+        assert_eq!(wrap_dim(320., 640, 0.), 320.);
+    }
+
+    #[test]
+    fn wrap_dim_wraps_once_the_offset_is_crossed() {
+        assert_eq!(wrap_dim(-44., 640, 45.), -44.);
+        assert_eq!(wrap_dim(-46., 640, 45.), 594.);
+        assert_eq!(wrap_dim(595., 640, 45.), -45.);
+    }
+
+    proptest! {
+        #[test]
+        fn wrap_dim_stays_within_the_limit(
+            value in -1e6..1e6f64,
+            limit in 1..5000i64,
+            offset in 0. ..100f64,
+        ) {
+            let wrapped = wrap_dim(value, limit, offset);
+            let offset = offset.round();
+            prop_assert!(wrapped >= -offset && wrapped < limit as f64 - offset);
+        }
+
+        #[test]
+        fn wrap_dim_moves_by_whole_limits(
+            value in -1e6..1e6f64,
+            limit in 1..5000i64,
+            offset in 0. ..100f64,
+        ) {
+            let moved = wrap_dim(value, limit, offset) - value;
+            let laps = (moved / limit as f64).round();
+            // up to a pixel off, positions are rounded
+            prop_assert!((moved - laps * limit as f64).abs() <= 1.);
+        }
+    }
+}
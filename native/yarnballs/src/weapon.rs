@@ -0,0 +1,106 @@
+// The weapons a ship can switch between. Every one fires missiles, which only differ by the
+// numbers below and by whether they pierce or home in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "nif", derive(rustler::NifUnitEnum))]
+pub enum Weapon {
+    #[default]
+    Single,
+    Spread,
+    Rapid,
+    Laser,
+    Homing,
+}
+
+pub struct Stats {
+    // in pixels per second
+    pub speed: f64,
+    // in milliseconds
+    pub lifespan: i64,
+    // dealt to the ships of other players, when they can be hit; enemies go down on any hit
+    pub damage: f64,
+    // minimum milliseconds between shots
+    pub interval: i64,
+    // added to the heat of the ship for every shot, see `ship::MAX_HEAT`
+    pub heat: f64,
+    pub radius: f64,
+    // missiles per shot, fanned out `spread` radians apart
+    pub shots: u32,
+    pub spread: f64,
+    // whether missiles go on through what they hit
+    pub pierces: bool,
+    // how fast missiles turn towards the nearest enemy, in radians per second
+    pub homing: f64,
+}
+
+const SINGLE: Stats = Stats {
+    speed: 500.,
+    lifespan: 1000,
+    damage: 25.,
+    interval: 200,
+    heat: 8.,
+    radius: 5.,
+    shots: 1,
+    spread: 0.,
+    pierces: false,
+    homing: 0.,
+};
+
+const SPREAD: Stats = Stats {
+    speed: 450.,
+    lifespan: 600,
+    damage: 15.,
+    interval: 400,
+    heat: 20.,
+    shots: 3,
+    spread: 0.25,
+    ..SINGLE
+};
+
+const RAPID: Stats = Stats {
+    speed: 600.,
+    lifespan: 700,
+    damage: 10.,
+    interval: 80,
+    heat: 6.,
+    radius: 4.,
+    ..SINGLE
+};
+
+const LASER: Stats = Stats {
+    speed: 1200.,
+    lifespan: 400,
+    damage: 40.,
+    interval: 600,
+    heat: 30.,
+    radius: 3.,
+    pierces: true,
+    ..SINGLE
+};
+
+const HOMING: Stats = Stats {
+    speed: 300.,
+    lifespan: 2000,
+    damage: 30.,
+    interval: 500,
+    heat: 20.,
+    radius: 6.,
+    homing: 3.,
+    ..SINGLE
+};
+
+impl Weapon {
+    pub fn stats(self) -> &'static Stats {
+        match self {
+            Self::Single => &SINGLE,
+            Self::Spread => &SPREAD,
+            Self::Rapid => &RAPID,
+            Self::Laser => &LASER,
+            Self::Homing => &HOMING,
+        }
+    }
+
+    // As written to snapshots.
+    pub fn kind(self) -> u8 {
+        self as u8
+    }
+}
@@ -0,0 +1,43 @@
+use crate::error::Error;
+use crate::snapshot::Frame;
+use crate::state::State;
+use rustler::{Resource, ResourceArc};
+use std::sync::{Mutex, MutexGuard};
+
+// A state kept on the native side, so that commands mutate it in place instead of copying the
+// whole world across the NIF boundary on every call.
+pub struct World {
+    state: Mutex<State>,
+    // the frame the last delta was taken against
+    last_frame: Mutex<Option<Frame>>,
+}
+
+#[rustler::resource_impl]
+impl Resource for World {}
+
+pub type Handle = ResourceArc<World>;
+
+impl World {
+    pub fn init(state: State) -> Handle {
+        ResourceArc::new(Self {
+            state: Mutex::new(state),
+            last_frame: Mutex::new(None),
+        })
+    }
+
+    pub fn state(&self) -> Result<MutexGuard<'_, State>, Error> {
+        self.state.lock().map_err(|_| Error::Poisoned)
+    }
+
+    // Delta since the previous call, or against an empty world on the first call.
+    pub fn delta(&self) -> Result<Vec<u8>, Error> {
+        let frame = self.state()?.frame();
+        let mut last_frame = self.last_frame.lock().map_err(|_| Error::Poisoned)?;
+        let delta = match &*last_frame {
+            Some(prev) => frame.delta(prev),
+            None => frame.delta(&Frame::empty()),
+        };
+        *last_frame = Some(frame);
+        Ok(delta)
+    }
+}
@@ -0,0 +1,24 @@
+defmodule Yarnballs.Effects do
+  @moduledoc """
+  Represents the effects on a ship.
+  """
+
+  @enforce_keys [:entities]
+  @derive {Jason.Encoder, only: [:entities]}
+  defstruct @enforce_keys
+end
+
+defmodule Yarnballs.Effect do
+  @moduledoc """
+  Represent an effect that lasts on a ship `until` the given simulation time.
+
+  `kind` is one of `:shield`, `:triple_shot`, `:speed_boost`, `:score_multiplier`,
+  `:invulnerable` or `:slow`. Slows come from bouncers running into the ship. Score multipliers
+  and slows grow stronger with every `stacks`, see `native/yarnballs/src/effect.rs` for how each
+  kind stacks.
+  """
+
+  @enforce_keys [:kind, :until, :stacks]
+  @derive {Jason.Encoder, only: @enforce_keys}
+  defstruct @enforce_keys
+end
@@ -7,6 +7,8 @@ defmodule Yarnballs.Enemy.Bouncer do
     :updated_at,
     :x,
     :y,
+    :prev_x,
+    :prev_y,
     :vel_x,
     :vel_y
   ]
@@ -36,6 +38,8 @@ defmodule Yarnballs.Enemy.Rock do
     :updated_at,
     :x,
     :y,
+    :prev_x,
+    :prev_y,
     :vel_x,
     :vel_y,
     :scale
@@ -15,12 +15,22 @@ defmodule Yarnballs.Explosion do
 
   @enforce_keys [
     :id,
+    :spawned_at,
     :updated_at,
     :x,
     :y,
     :size,
     :lifespan
   ]
-  @derive {Jason.Encoder, only: [:id, :x, :y, :size]}
   defstruct @enforce_keys
+
+  defimpl Jason.Encoder do
+    # the native side keeps ids as pairs of integers, clients take them as strings
+    def encode(%{id: {hi, lo}} = explosion, opts) do
+      explosion
+      |> Map.take([:x, :y, :size])
+      |> Map.put(:id, Base.encode16(<<hi::64, lo::64>>, case: :lower))
+      |> Jason.Encode.map(opts)
+    end
+  end
 end
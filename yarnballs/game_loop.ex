@@ -1,99 +1,199 @@
 defmodule Yarnballs.GameLoop do
   @moduledoc """
-  Background process that updates the game loop.
+  Background process that updates the game loop of a room.
+
+  Loops are started on demand under a `DynamicSupervisor` and registered by room id in a
+  `Registry`, both of which the using module names:
+
+      use Yarnballs.GameLoop,
+        registry: MyApp.GameLoopRegistry,
+        supervisor: MyApp.GameLoopSupervisor
+
+  A loop keeps track of the players in its room, and once nobody has been in for
+  `:idle_timeout` milliseconds (30 seconds unless given) it stops and destroys the room.
   """
 
-  defmacro __using__(_opts) do
+  defmacro __using__(opts) do
+    registry = Keyword.fetch!(opts, :registry)
+    supervisor = Keyword.fetch!(opts, :supervisor)
+    idle_timeout = Keyword.get(opts, :idle_timeout, 30_000)
+
     quote do
-      use GenServer
+      use GenServer, restart: :transient
       require Logger
-      alias Yarnballs.State
+      alias Yarnballs.{Rooms, State}
 
       # CLIENT
 
-      def start_link(state) do
-        GenServer.start_link(__MODULE__, state, name: :game_loop)
+      def start_link({room_id, opts}) do
+        GenServer.start_link(__MODULE__, {room_id, opts}, name: via(room_id))
+      end
+
+      @doc """
+      Starts the loop of the room unless it runs already. `opts` are only used to create the room,
+      see `Yarnballs.State.init/1`.
+      """
+      def ensure_started(room_id, opts \\ []) do
+        case DynamicSupervisor.start_child(unquote(supervisor), {__MODULE__, {room_id, opts}}) do
+          {:ok, pid} -> {:ok, pid}
+          {:error, {:already_started, pid}} -> {:ok, pid}
+          error -> error
+        end
       end
 
-      def join(id, name) do
-        GenServer.cast(:game_loop, {:join, id, name})
+      def stop(room_id) do
+        GenServer.stop(via(room_id))
       end
 
-      def leave(id) do
-        GenServer.cast(:game_loop, {:leave, id})
+      def join(room_id, id, name, team \\ nil) do
+        GenServer.cast(via(room_id), {:join, id, name, team})
       end
 
-      def turned_ship(id, clockwise) do
-        GenServer.cast(:game_loop, {:turn_ship, id, clockwise})
+      def leave(room_id, id) do
+        GenServer.cast(via(room_id), {:leave, id})
       end
 
-      def thrusted_ship(id) do
-        GenServer.cast(:game_loop, {:thrust_ship, id})
+      def turned_ship(room_id, id, clockwise) do
+        GenServer.cast(via(room_id), {:turn_ship, id, clockwise})
       end
 
-      def fire_missile(shooter_id) do
-        GenServer.cast(:game_loop, {:fire_missile, shooter_id})
+      def thrusted_ship(room_id, id) do
+        GenServer.cast(via(room_id), {:thrust_ship, id})
       end
 
+      def fire_missile(room_id, shooter_id) do
+        GenServer.cast(via(room_id), {:fire_missile, shooter_id})
+      end
+
+      def switch_weapon(room_id, id, weapon) do
+        GenServer.cast(via(room_id), {:switch_weapon, id, weapon})
+      end
+
+      defp via(room_id), do: {:via, Registry, {unquote(registry), room_id}}
+
       # SERVER
 
       @impl true
-      @spec init(State.t()) :: {:ok, State.t()}
-      def init(state) do
-        schedule_tick()
-        {:ok, state}
+      def init({room_id, opts}) do
+        Process.flag(:trap_exit, true)
+
+        # a loop that crashed picks up the world it left behind, see `terminate/2`
+        result =
+          case Rooms.create(room_id, opts) do
+            {:error, {:room_taken, _}} -> Rooms.fetch(room_id)
+            result -> result
+          end
+
+        case result do
+          {:ok, state} ->
+            started_at = now()
+            schedule_tick(started_at)
+            {:ok,
+             %{room_id: room_id, state: state, players: MapSet.new(), empty_since: started_at}}
+
+          {:error, reason} ->
+            {:stop, reason}
+        end
       end
 
       @impl true
-      def handle_cast({:join, id, name}, state) do
-        {:noreply, State.spawn_ship(state, id, name)}
+      def handle_cast({:join, id, name, team}, loop) do
+        loop = %{loop | players: MapSet.put(loop.players, id), empty_since: nil}
+        loop.state |> State.spawn_ship(id, name, team) |> reply(loop)
       end
 
       @impl true
-      def handle_cast({:leave, id}, state) do
-        Logger.info("player '#{id}' is leaving the game")
-        {:noreply, State.remove_ship(state, id)}
+      def handle_cast({:leave, id}, loop) do
+        Logger.info("player '#{id}' is leaving room '#{loop.room_id}'")
+        players = MapSet.delete(loop.players, id)
+        empty_since = if MapSet.size(players) == 0, do: loop.empty_since || now()
+        loop = %{loop | players: players, empty_since: empty_since}
+        loop.state |> State.remove_ship(id) |> reply(loop)
       end
 
       @impl true
-      def handle_cast({:turn_ship, id, clockwise}, state) do
-        {:noreply, State.turn_ship(state, id, clockwise)}
+      def handle_cast({:turn_ship, id, clockwise}, loop) do
+        loop.state |> State.turn_ship(id, clockwise) |> reply(loop)
       end
 
       @impl true
-      def handle_cast({:thrust_ship, id}, state) do
-        {:noreply, State.thrust_ship(state, id)}
+      def handle_cast({:thrust_ship, id}, loop) do
+        loop.state |> State.thrust_ship(id) |> reply(loop)
       end
 
       @impl true
-      def handle_cast({:fire_missile, shooter_id}, state) do
-        {:noreply, State.spawn_missile(state, shooter_id)}
+      def handle_cast({:fire_missile, shooter_id}, loop) do
+        loop.state |> State.spawn_missile(shooter_id) |> reply(loop)
       end
 
       @impl true
-      def handle_info(:tick, state) do
-        new_state = State.update(state)
-
-        handle_new_state(new_state)
-        schedule_tick()
+      def handle_cast({:switch_weapon, id, weapon}, loop) do
+        loop.state |> State.switch_weapon(id, weapon) |> reply(loop)
+      end
 
-        {:noreply, new_state}
+      @impl true
+      def handle_info({:tick, last_ticked_at}, loop) do
+        ticked_at = now()
+
+        if idle?(loop, ticked_at) do
+          Logger.info("room '#{loop.room_id}' has been empty for a while, closing it")
+          {:stop, :normal, loop}
+        else
+          schedule_tick(ticked_at)
+
+          case State.update(loop.state, ticked_at - last_ticked_at) do
+            {:ok, new_state} ->
+              handle_new_state(loop.room_id, new_state)
+              {:noreply, %{loop | state: new_state}}
+
+            error ->
+              reply(error, loop)
+          end
+        end
       end
 
-      def handle_new_state(_state), do: nil
+      def handle_new_state(_room_id, _state), do: nil
+
+      @impl true
+      def terminate(reason, loop) do
+        # keep the world of a crashed loop around for its restart
+        case reason do
+          :normal -> Rooms.destroy(loop.room_id)
+          :shutdown -> Rooms.destroy(loop.room_id)
+          {:shutdown, _} -> Rooms.destroy(loop.room_id)
+          _ -> :ok
+        end
+      end
 
       @impl true
-      def code_change(_old_vsn, _state, _extra) do
-        Logger.debug("reseting game state")
+      def code_change(_old_vsn, loop, _extra) do
+        Logger.debug("reseting game state of room '#{loop.room_id}'")
+
+        {:ok, config} = Rooms.config(loop.room_id)
+        :ok = Rooms.destroy(loop.room_id)
+        {:ok, state} = Rooms.create(loop.room_id, Map.to_list(config))
 
-        {:ok, State.init()}
+        {:ok, %{loop | state: state}}
       end
 
-      defp schedule_tick do
-        Process.send_after(self(), :tick, 16)
+      # Commands that fail, for example because the ship is gone already, leave the state as is.
+      defp reply({:ok, new_state}, loop), do: {:noreply, %{loop | state: new_state}}
+
+      defp reply({:error, reason}, loop) do
+        Logger.warning("state of room '#{loop.room_id}' was not updated: #{inspect(reason)}")
+        {:noreply, loop}
       end
 
-      defoverridable(handle_new_state: 1)
+      defp idle?(%{empty_since: nil}, _now), do: false
+      defp idle?(%{empty_since: empty_since}, now), do: now - empty_since >= unquote(idle_timeout)
+
+      defp schedule_tick(ticked_at) do
+        Process.send_after(self(), {:tick, ticked_at}, 16)
+      end
+
+      defp now, do: System.monotonic_time(:millisecond)
+
+      defoverridable(handle_new_state: 2)
     end
   end
 end
@@ -17,10 +17,14 @@ defmodule Yarnballs.Missile do
     :updated_at,
     :x,
     :y,
+    :prev_x,
+    :prev_y,
     :vel_x,
     :vel_y,
-    :lifespan
+    :lifespan,
+    :weapon,
+    :pierced
   ]
-  @derive {Jason.Encoder, only: [:x, :y, :vel_x, :vel_y]}
+  @derive {Jason.Encoder, only: [:x, :y, :vel_x, :vel_y, :weapon]}
   defstruct @enforce_keys
 end
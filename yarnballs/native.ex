@@ -5,14 +5,38 @@ defmodule Yarnballs.Native do
   use Rustler, otp_app: :shmup, crate: :yarnballs
 
   # state
-  def init_state(), do: :erlang.nif_error(:nif_not_loaded)
-  def spawn_ship(_s, _id, _n), do: :erlang.nif_error(:nif_not_loaded)
+  def init_state(_config), do: :erlang.nif_error(:nif_not_loaded)
+  def init_recorded_state(_config), do: :erlang.nif_error(:nif_not_loaded)
+  def spawn_ship(_s, _id, _n, _t), do: :erlang.nif_error(:nif_not_loaded)
   def turn_ship(_s, _id, _b), do: :erlang.nif_error(:nif_not_loaded)
   def thrust_ship(_s, _id), do: :erlang.nif_error(:nif_not_loaded)
   def remove_ship(_s, _id), do: :erlang.nif_error(:nif_not_loaded)
+  def switch_weapon(_s, _id, _w), do: :erlang.nif_error(:nif_not_loaded)
   def fire_missile_or_respawn(_s, _id), do: :erlang.nif_error(:nif_not_loaded)
   def update_bodies(_s), do: :erlang.nif_error(:nif_not_loaded)
+  def update_bodies(_s, _dt), do: :erlang.nif_error(:nif_not_loaded)
   def level(_s), do: :erlang.nif_error(:nif_not_loaded)
+  def level(_s, _team), do: :erlang.nif_error(:nif_not_loaded)
   def total_score(_s), do: :erlang.nif_error(:nif_not_loaded)
+  def total_score(_s, _team), do: :erlang.nif_error(:nif_not_loaded)
   def next_level_score(_s), do: :erlang.nif_error(:nif_not_loaded)
+  def next_level_score(_s, _team), do: :erlang.nif_error(:nif_not_loaded)
+  def drain_events(_s), do: :erlang.nif_error(:nif_not_loaded)
+
+  # rendering
+  def render(_s), do: :erlang.nif_error(:nif_not_loaded)
+  def snapshot(_s), do: :erlang.nif_error(:nif_not_loaded)
+  def delta(_s), do: :erlang.nif_error(:nif_not_loaded)
+
+  # rooms
+  def create_world(_room_id, _config), do: :erlang.nif_error(:nif_not_loaded)
+  def fetch_world(_room_id), do: :erlang.nif_error(:nif_not_loaded)
+  def world_config(_room_id), do: :erlang.nif_error(:nif_not_loaded)
+  def list_worlds(), do: :erlang.nif_error(:nif_not_loaded)
+  def destroy_world(_room_id), do: :erlang.nif_error(:nif_not_loaded)
+
+  # replay
+  def recording(_s), do: :erlang.nif_error(:nif_not_loaded)
+  def replay(_r), do: :erlang.nif_error(:nif_not_loaded)
+  def replay(_r, _until), do: :erlang.nif_error(:nif_not_loaded)
 end
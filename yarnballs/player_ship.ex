@@ -11,14 +11,22 @@ end
 defmodule Yarnballs.PlayerShip do
   @moduledoc """
   Represent ships operated by players.
+
+  Ships fire again from `ready_at` on, in simulation time, unless `overheated`. Every shot adds
+  to `heat`, which cools down over time; an overheated ship fires again once it is back at 0.
+  `effects` holds what is left of the power-ups it picked up, and of the invulnerability it
+  gets for a few seconds after respawning, see `Yarnballs.Effects`.
   """
 
   @enforce_keys [
     :id,
     :name,
+    :team,
     :updated_at,
     :x,
     :y,
+    :prev_x,
+    :prev_y,
     :vel_x,
     :vel_y,
     :angle,
@@ -27,7 +35,12 @@ defmodule Yarnballs.PlayerShip do
     :thrusting,
     :health,
     :score,
-    :destroyed_at
+    :destroyed_at,
+    :weapon,
+    :ready_at,
+    :heat,
+    :overheated,
+    :effects
   ]
   @derive {Jason.Encoder, only: @enforce_keys}
   defstruct @enforce_keys
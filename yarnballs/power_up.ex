@@ -0,0 +1,29 @@
+defmodule Yarnballs.PowerUps do
+  @moduledoc """
+  Represents a collection of power-ups lying around in the arena.
+  """
+
+  @enforce_keys [:entities, :remove_ids]
+  @derive {Jason.Encoder, only: [:entities]}
+  defstruct @enforce_keys
+end
+
+defmodule Yarnballs.PowerUp do
+  @moduledoc """
+  Represent a power-up left behind by a destroyed enemy, for the first ship that runs into it.
+
+  `kind` is one of `:shield`, `:health_pack`, `:triple_shot`, `:speed_boost` or
+  `:score_multiplier`.
+  """
+
+  @enforce_keys [
+    :id,
+    :kind,
+    :updated_at,
+    :x,
+    :y,
+    :lifespan
+  ]
+  @derive {Jason.Encoder, only: [:kind, :x, :y]}
+  defstruct @enforce_keys
+end
@@ -0,0 +1,17 @@
+defmodule Yarnballs.Recording do
+  @moduledoc """
+  Represents every command applied to a game state since it was initialized.
+  """
+
+  @enforce_keys [:config, :entries]
+  defstruct @enforce_keys
+end
+
+defmodule Yarnballs.Recording.Entry do
+  @moduledoc """
+  Represents a command and the simulation time once it was applied.
+  """
+
+  @enforce_keys [:at, :command]
+  defstruct @enforce_keys
+end
@@ -0,0 +1,36 @@
+defmodule Yarnballs.Rooms do
+  @moduledoc """
+  Registry of game states by room id, kept on the native side so that one node can host many
+  matches at once.
+
+  The states returned work with every function in `Yarnballs.State`. Destroying a room only
+  unregisters it, states already handed out keep working until they are garbage collected.
+  """
+  alias Yarnballs.{Native, State}
+
+  @doc """
+  Creates a state for the room, see `Yarnballs.State.init/1` for options.
+
+  Returns `{:error, {:room_taken, room_id}}` when the room exists already.
+  """
+  def create(room_id, opts \\ []), do: Native.create_world(room_id, State.config(opts))
+
+  @doc """
+  Returns the state of the room, or `{:error, {:unknown_room, room_id}}`.
+  """
+  def fetch(room_id), do: Native.fetch_world(room_id)
+
+  @doc """
+  Returns the config the room was created with, including the seed that was picked for it.
+  """
+  def config(room_id), do: Native.world_config(room_id)
+
+  @doc """
+  Lists the ids of all rooms, in order.
+  """
+  def list, do: Native.list_worlds()
+
+  def destroy(room_id) do
+    with {:ok, _room_id} <- Native.destroy_world(room_id), do: :ok
+  end
+end
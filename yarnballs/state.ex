@@ -1,34 +1,107 @@
 defmodule Yarnballs.State do
   @moduledoc """
   Represents the Yarnballs game state.
+
+  The state itself lives in a native resource. The functions in this module take and return a
+  handle to it, and commands mutate it in place. Use `render/1`, `snapshot/1` or `delta/1` to
+  get at its contents.
+
+  Every function returns `{:ok, value}` or `{:error, reason}`, where `reason` is one of
+
+    * `{:unknown_ship, id}` - no ship with the given id is in the game
+    * `{:unknown_room, room_id}`, `{:room_taken, room_id}` - see `Yarnballs.Rooms`
+    * `{:invalid_config, reasons}` - the level document does not parse or is invalid
+    * `{:invalid_arena, %{width: width, height: height}}` - the arena size is out of range
+    * `{:invalid_cell_size, cell_size}` - the collision grid would be too fine for the arena
+    * `{:invalid_ship_bounce, bounce}` - the ship bounce is not between 0 and 1
+    * `{:invalid_power_up_chance, chance}` - the power-up chance is not between 0 and 1
+    * `{:decode, argument}` - an argument has the wrong shape
+    * `:poisoned` - an earlier call crashed while holding the state, it is unusable
+    * `:out_of_memory` - the binary returned by `snapshot/1` or `delta/1` could not be allocated
+
+  See `native/yarnballs/src/error.rs`.
   """
   alias Yarnballs.Native
 
   @enforce_keys [
     :missiles,
     :enemies,
-    :ships
+    :ships,
+    :power_ups,
+    :level,
+    :score,
+    :start_level_score,
+    :next_level_score,
+    :width,
+    :height
   ]
+  @derive {Jason.Encoder, only: @enforce_keys}
   defstruct @enforce_keys
 
-  defimpl Jason.Encoder do
-    alias Yarnballs.Native
+  @doc """
+  Initializes a state.
+
+  Returns `{:error, {:invalid_config, reasons}}` when the level document does not parse or is
+  invalid, for example `{:error, {:invalid_config, [{:no_spawners, %{level: 2}}]}}`. See
+  `native/yarnballs/src/level.rs` for the reasons.
+
+  ## Options
+
+    * `:seed` - seeds the random number generator, a random seed is used when absent
+    * `:levels` - a TOML level document, see `native/yarnballs/src/levels.toml`
+    * `:width`, `:height` - the arena size in pixels, between 1 and 8000, 640 by 480 by default
+    * `:cell_size` - the collision grid cell size in pixels, 50 by default
+    * `:ship_bounce` - how much of their closing speed ships keep when they bump into each other,
+      from 0 to 1 for a fully elastic bounce; ships pass through each other by default
+    * `:pvp` - whether missiles hit the ships of other players, `false` by default
+    * `:friendly_fire` - whether those missiles hit teammates too, `false` by default; shooting
+      a teammate down scores nothing
+    * `:power_up_chance` - how likely a destroyed enemy is to leave a power-up behind, from 0
+      to 1, 0.1 by default; see `Yarnballs.PowerUp`
+
+  """
+  def init(opts \\ []), do: opts |> config() |> Native.init_state()
 
-    def encode(value, opts) do
-      {start_level_score, next_level_score} = Native.next_level_score(value)
+  def init!(opts \\ []) do
+    case init(opts) do
+      {:ok, state} -> state
+      {:error, {:invalid_config, reasons}} ->
+        raise ArgumentError, "invalid level document: #{inspect(reasons)}"
 
-      value
-      |> Map.put(:level, Native.level(value))
-      |> Map.put(:score, Native.total_score(value))
-      |> Map.put(:start_level_score, start_level_score)
-      |> Map.put(:next_level_score, next_level_score)
-      |> Jason.Encode.map(opts)
+      {:error, reason} ->
+        raise ArgumentError, "could not initialize state: #{inspect(reason)}"
     end
   end
 
-  def init(), do: Native.init_state()
+  @doc """
+  Initializes a state that records every command applied to it, see `init/1` for options.
+  """
+  def init_recorded(opts \\ []), do: opts |> config() |> Native.init_recorded_state()
+
+  @doc false
+  def config(opts) do
+    %{
+      seed: Keyword.get(opts, :seed),
+      levels: Keyword.get(opts, :levels),
+      width: opts |> Keyword.get(:width) |> float(),
+      height: opts |> Keyword.get(:height) |> float(),
+      cell_size: opts |> Keyword.get(:cell_size) |> float(),
+      ship_bounce: opts |> Keyword.get(:ship_bounce) |> float(),
+      pvp: Keyword.get(opts, :pvp),
+      friendly_fire: Keyword.get(opts, :friendly_fire),
+      power_up_chance: opts |> Keyword.get(:power_up_chance) |> float()
+    }
+  end
+
+  defp float(nil), do: nil
+  defp float(value), do: value / 1
 
-  def spawn_ship(state, id, name), do: Native.spawn_ship(state, id, name)
+  @doc """
+  Spawns a ship, in `team` when given. Teammates share a score and level progression, see
+  `Yarnballs.Native.total_score/2`, `Yarnballs.Native.level/2` and
+  `Yarnballs.Native.next_level_score/2`.
+  """
+  def spawn_ship(state, id, name, team \\ nil), do: Native.spawn_ship(state, id, name, team)
 
   def remove_ship(state, id), do: Native.remove_ship(state, id)
 
@@ -36,9 +109,55 @@ defmodule Yarnballs.State do
 
   def thrust_ship(state, id), do: Native.thrust_ship(state, id)
 
+  @doc """
+  Fires the weapon of the ship, or respawns it when it is down. Nothing is fired while the
+  weapon is not ready for the next shot yet or overheated, see `Yarnballs.PlayerShip`.
+  """
   def spawn_missile(state, shooter_id) do
     Native.fire_missile_or_respawn(state, shooter_id)
   end
 
+  @doc """
+  Arms the ship with `weapon`, one of `:single`, `:spread`, `:rapid`, `:laser` or `:homing`.
+  See `native/yarnballs/src/weapon.rs` for how fast, far and hard each one fires.
+  """
+  def switch_weapon(state, id, weapon), do: Native.switch_weapon(state, id, weapon)
+
   def update(state), do: Native.update_bodies(state)
+
+  def update(state, dt), do: Native.update_bodies(state, dt)
+
+  @doc """
+  Takes the events that happened since the previous call out of the state, oldest first. Only
+  the latest 256 are kept.
+
+  Events are
+
+    * `{:kill, %{killer_id: id, victim_id: id, at: time}}` - a ship shot another one down, in
+      rooms started with `pvp: true`
+
+  """
+  def drain_events(state), do: Native.drain_events(state)
+
+  @doc """
+  Materialises the state as a `Yarnballs.State` struct.
+  """
+  def render(state), do: Native.render(state)
+
+  @doc """
+  Encodes the state as a versioned binary, see `native/yarnballs/src/snapshot.rs` for the layout.
+  """
+  def snapshot(state), do: Native.snapshot(state)
+
+  @doc """
+  Encodes the entities that spawned, changed or were removed since the previous call, in the
+  same binary format as `snapshot/1`.
+  """
+  def delta(state), do: Native.delta(state)
+
+  def recording(state), do: Native.recording(state)
+
+  def replay(recording), do: Native.replay(recording)
+
+  def replay(recording, until), do: Native.replay(recording, until)
 end